@@ -1,19 +1,137 @@
+use core::fmt;
+use core::ops::Range;
+
+use zeroize::ZeroizeOnDrop;
 
 pub mod b128;
 
+pub mod b128t;
+
 pub mod b256;
 
+#[cfg(feature = "std")]
 pub mod util;
 
+#[cfg(not(feature = "scalar"))]
 pub mod primitives;
 
-#[derive(PartialEq)]
+pub mod dispatch;
+
+pub mod scalar;
+
+pub mod schedule;
+
+mod backend;
+
+#[cfg(not(feature = "scalar"))]
+use self::primitives as engine;
+
+#[cfg(feature = "scalar")]
+use self::scalar as engine;
+
+/// Upper bound for `Rounds::Custom`.
+pub const MAX_ROUNDS: usize = 32;
+
+#[derive(PartialEq, Default, Clone, Copy, Debug)]
 pub enum Rounds {
     FASTER,
-    SAFER
+    #[default]
+    SAFER,
+    Custom(usize)
 }
 
+impl Rounds {
 
+    /// Indices of the round keys used by this setting, given the number of
+    /// round keys the preset schedule of a variant derives.
+    pub(crate) fn key_range(&self, round_keys: usize) -> Result<Range<usize>, Error> {
+        match *self {
+            Rounds::FASTER => Ok(2..round_keys),
+            Rounds::SAFER => Ok(0..round_keys),
+            Rounds::Custom(n) if (1..=MAX_ROUNDS).contains(&n) => Ok(0..n),
+            Rounds::Custom(n) => Err(Error::InvalidRounds(n)),
+        }
+    }
+}
+
+
+/// Versions of the key schedule. `V1` is kept so that existing ciphertexts still
+/// decrypt; new deployments should use `V2`. `V1` stays the default of `Flags`,
+/// while `KeyInit::new` keys `Tekton128` and `Tekton256` with `V2`.
+#[derive(PartialEq, Default, Clone, Copy, Debug)]
+pub enum KeySchedule {
+    /// Byte-wise `(k << i) * 113`, round keys only depend on the matching master key byte.
+    #[default]
+    V1,
+    /// Round keys chained through `encrypt_round` with round constants, every
+    /// round key depends on the whole master key. Tekton256 seeds the chain of
+    /// each key half with the other half and alternates between the chains, so
+    /// all 256 bits count in every round key.
+    V2
+}
+
+
+/// `Flags::default()` picks `KeySchedule::V1` for compatibility, `Flags::v2()`
+/// the settings `KeyInit::new` uses.
+#[derive(Default)]
 pub struct Flags {
     pub rounds: Rounds,
-}
\ No newline at end of file
+    pub schedule: KeySchedule,
+}
+
+impl Flags {
+
+    /// Default rounds with `KeySchedule::V2`.
+    pub fn v2() -> Flags {
+        Flags { schedule: KeySchedule::V2, ..Default::default() }
+    }
+}
+
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Error {
+    InvalidRounds(usize),
+    UnsupportedBackend(dispatch::Backend),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidRounds(n) => write!(f, "invalid number of rounds: {} (expected 1..={})", n, MAX_ROUNDS),
+            Error::UnsupportedBackend(b) => write!(f, "backend not supported by this CPU: {:?}", b),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+
+/// Implementors wipe their round keys on drop, keep them out of `Debug` and
+/// can be cloned and shared between threads.
+pub trait TektonCipher: Sized + Clone + fmt::Debug + Send + Sync + ZeroizeOnDrop {
+    type Key;
+
+    /// Number of round keys derived for the preset `Rounds`; `Rounds::SAFER` uses all of them.
+    const ROUND_KEYS: usize;
+
+    fn try_new(key: Self::Key, flags: Flags) -> Result<Self, Error>;
+
+    /// Panics if `flags.rounds` is out of range, see `try_new`.
+    fn new(key: Self::Key, flags: Flags) -> Self {
+        match Self::try_new(key, flags) {
+            Ok(cipher) => cipher,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    fn rounds(&self) -> usize;
+
+    fn encrypt_block(&self, payload: &mut [u8; 16]);
+
+    fn decrypt_block(&self, cipher: &mut [u8; 16]);
+
+    /// Same as `encrypt_block` on every block, processing several blocks at once in wide SIMD lanes.
+    fn encrypt_blocks(&self, blocks: &mut [[u8; 16]]);
+
+    fn decrypt_blocks(&self, blocks: &mut [[u8; 16]]);
+}
@@ -1,77 +1,177 @@
-use std::simd;
+use core::fmt;
 
-#[allow(unused)]
+use cipher::{
+    consts::{U16}, BlockCipher, BlockClosure, BlockDecrypt, BlockEncrypt, BlockSizeUser, Key,
+    KeyInit, KeySizeUser,
+};
+
+#[cfg(test)]
 use rand::{Rng};
 
-use crate::imp::{Flags};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+use crate::imp::{Error, Flags, KeySchedule, TektonCipher, MAX_ROUNDS};
 
-use super::{primitives::*, Rounds};
+use super::{backend::*, engine, schedule::*};
 
-use simd::Simd;
 
 
+/// Round keys are wiped when the cipher is dropped and never show up in `Debug`.
+/// The cipher is `Send + Sync`, a single instance can be shared by worker threads.
+#[derive(Clone)]
 pub struct Tekton128 {
-    keys: [Simd<u8, 16>; 5],
-    flags: Flags
+    keys: [[u8; 16]; MAX_ROUNDS],
+    rounds: usize
 }
 
 impl Tekton128 {
 
+    /// Panics if `flags.rounds` is out of range, see `try_new`.
     pub fn new(key: [u8; 16], flags: Flags) -> Tekton128{
+        return <Tekton128 as TektonCipher>::new(key, flags);
+    }
 
-        let mut keys: [Simd<u8, 16>; 5] = [simd::u8x16::splat(0); 5];
+    pub fn try_new(key: [u8; 16], flags: Flags) -> Result<Tekton128, Error>{
+        let range = flags.rounds.key_range(Self::ROUND_KEYS)?;
 
-        for i in 0..5 {
-            let bytes = key.map(|x| (x << i).wrapping_mul(113));
-            let ki = simd::u8x16::from_array(bytes);
-            keys[i] = ki;
-        }
+        let mut tekton = Tekton128 {
+            keys: [[0; 16]; MAX_ROUNDS],
+            rounds: range.len()
+        };
+        let keys = &mut tekton.keys;
 
-        return Tekton128 {
-            keys,
-            flags
+        match flags.schedule {
+            KeySchedule::V1 => {
+                for (ki, i) in keys.iter_mut().zip(range.clone()) {
+                    *ki = v1_round_key(key, i);
+                }
+            },
+            KeySchedule::V2 => {
+                let mut k = key;
+                for i in 0..range.end {
+                    k = v2_round_key(k, i);
+                    if i >= range.start {
+                        keys[i - range.start] = k;
+                    }
+                }
+                k.zeroize();
+            }
         }
+
+        return Ok(tekton)
     }
 
-    
+    #[inline]
+    pub(crate) fn round_keys(&self) -> &[[u8; 16]] {
+        return &self.keys[..self.rounds];
+    }
 
     #[inline]
     pub fn encrypt(&self, payload: &mut [u8; 16]){
+        engine::encrypt_block(self.round_keys(), payload);
+    }
+
+    #[inline]
+    pub fn decrypt(&self, cipher: &mut [u8; 16]){
+        engine::decrypt_block(self.round_keys(), cipher);
+    }
 
+    #[inline]
+    pub fn encrypt_blocks(&self, blocks: &mut [[u8; 16]]){
+        engine::encrypt_blocks(self.round_keys(), blocks);
+    }
 
-        let mut state = simd::u8x16::from_array(*payload);
-        
-        if self.flags.rounds == Rounds::SAFER {
-            state = encrypt_round(state, self.keys[0]);
-            state = encrypt_round(state, self.keys[1]); 
+    #[inline]
+    pub fn decrypt_blocks(&self, blocks: &mut [[u8; 16]]){
+        engine::decrypt_blocks(self.round_keys(), blocks);
+    }
+}
+
+
+impl Drop for Tekton128 {
+    fn drop(&mut self) {
+        for k in self.keys.iter_mut() {
+            k.zeroize();
         }
-        
-        state = encrypt_round(state, self.keys[2]);
-        state = encrypt_round(state, self.keys[3]);
-        state = encrypt_round(state, self.keys[4]);
-        
-        
-        *payload = *state.as_array();
-        
+    }
+}
+
+impl ZeroizeOnDrop for Tekton128 {}
+
+impl fmt::Debug for Tekton128 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tekton128")
+            .field("rounds", &self.rounds)
+            .finish_non_exhaustive()
+    }
+}
+
+
+impl TektonCipher for Tekton128 {
+    type Key = [u8; 16];
+
+    const ROUND_KEYS: usize = 5;
+
+    fn try_new(key: [u8; 16], flags: Flags) -> Result<Tekton128, Error> {
+        Tekton128::try_new(key, flags)
+    }
 
+    fn rounds(&self) -> usize {
+        self.rounds
     }
 
     #[inline]
-    pub fn decrypt(&self, cipher: &mut [u8; 16]){
-       
-        let mut state = simd::u8x16::from_array(*cipher);
-        
-        state = decrypt_round(state, self.keys[4]);
-        state = decrypt_round(state, self.keys[3]);
-        state = decrypt_round(state, self.keys[2]);
-        
-        if self.flags.rounds == Rounds::SAFER {
-            state = decrypt_round(state, self.keys[1]);
-            state = decrypt_round(state, self.keys[0]);
-        }
+    fn encrypt_block(&self, payload: &mut [u8; 16]) {
+        self.encrypt(payload)
+    }
 
-        *cipher = *state.as_array();
-    
+    #[inline]
+    fn decrypt_block(&self, cipher: &mut [u8; 16]) {
+        self.decrypt(cipher)
+    }
+
+    #[inline]
+    fn encrypt_blocks(&self, blocks: &mut [[u8; 16]]) {
+        self.encrypt_blocks(blocks)
+    }
+
+    #[inline]
+    fn decrypt_blocks(&self, blocks: &mut [[u8; 16]]) {
+        self.decrypt_blocks(blocks)
+    }
+}
+
+
+impl BlockSizeUser for Tekton128 {
+    type BlockSize = U16;
+}
+
+impl KeySizeUser for Tekton128 {
+    type KeySize = U16;
+}
+
+impl BlockCipher for Tekton128 {}
+
+/// Keys with `Flags::v2()` rather than `Flags::default()`: `KeyInit` has no
+/// `V1` ciphertexts to keep decrypting, so it starts out on the newer schedule.
+/// A mode built with `Mode::<Tekton128>::new(key)` is the same as one built from
+/// `Tekton128::new(key, Flags::v2())`, other settings go through the mode's
+/// `From<Tekton128>`.
+impl KeyInit for Tekton128 {
+    fn new(key: &Key<Self>) -> Self {
+        Tekton128::new((*key).into(), Flags::v2())
+    }
+}
+
+impl BlockEncrypt for Tekton128 {
+    fn encrypt_with_backend(&self, f: impl BlockClosure<BlockSize = U16>) {
+        f.call(&mut TektonEnc(self))
+    }
+}
+
+impl BlockDecrypt for Tekton128 {
+    fn decrypt_with_backend(&self, f: impl BlockClosure<BlockSize = U16>) {
+        f.call(&mut TektonDec(self))
     }
 }
 
@@ -100,13 +200,185 @@ fn test_encrypt_decrypt(){
 
     test_in_loop(
         Tekton128::new(key.to_be_bytes(), 
-        Flags {
-            rounds: Rounds::FASTER }));
+        Flags { rounds: Rounds::FASTER, ..Default::default() }));
 
     test_in_loop(
         Tekton128::new(key.to_be_bytes(), 
-        Flags {
-            rounds: Rounds::SAFER }));
+        Flags { rounds: Rounds::SAFER, ..Default::default() }));
 
     
-}
\ No newline at end of file
+}
+#[test]
+fn test_known_answer(){
+    use crate::imp::{Flags, Rounds};
+    let key: [u8; 16] = core::array::from_fn(|i| i as u8);
+    let payload: [u8; 16] = core::array::from_fn(|i| (0x11 * i) as u8);
+
+    let mut enc = payload;
+    Tekton128::new(key, Flags { rounds: Rounds::FASTER, ..Default::default() }).encrypt(&mut enc);
+    assert_eq!(enc, [0xfb, 0xfb, 0x25, 0xa4, 0x6b, 0x38, 0xee, 0x7f, 0xce, 0x5f, 0x40, 0x3b, 0xdb, 0x76, 0x96, 0xa1]);
+
+    let mut enc = payload;
+    Tekton128::new(key, Flags { rounds: Rounds::SAFER, ..Default::default() }).encrypt(&mut enc);
+    assert_eq!(enc, [0xa6, 0xaf, 0xa7, 0x2f, 0x64, 0x85, 0x21, 0x99, 0xb5, 0x58, 0x2f, 0x97, 0x91, 0x36, 0x7f, 0xf4]);
+}
+
+#[test]
+fn test_custom_rounds(){
+    use crate::imp::{Flags, Rounds};
+    let key: u128 = rand::thread_rng().gen();
+    let p: u128 = rand::thread_rng().gen();
+
+    for n in [1, 4, 12, MAX_ROUNDS] {
+        let tekton = Tekton128::new(key.to_be_bytes(), Flags { rounds: Rounds::Custom(n), ..Default::default() });
+        assert_eq!(tekton.rounds(), n);
+
+        let mut enc = p.to_be_bytes();
+        tekton.encrypt(&mut enc);
+        tekton.decrypt(&mut enc);
+        assert_eq!(enc, p.to_be_bytes());
+    }
+
+    let mut safer = p.to_be_bytes();
+    Tekton128::new(key.to_be_bytes(), Flags { rounds: Rounds::SAFER, ..Default::default() }).encrypt(&mut safer);
+    let mut custom = p.to_be_bytes();
+    Tekton128::new(key.to_be_bytes(), Flags { rounds: Rounds::Custom(5), ..Default::default() }).encrypt(&mut custom);
+    assert_eq!(safer, custom);
+
+    let mut longer = p.to_be_bytes();
+    Tekton128::new(key.to_be_bytes(), Flags { rounds: Rounds::Custom(12), ..Default::default() }).encrypt(&mut longer);
+    assert_ne!(safer, longer);
+
+    assert_eq!(Tekton128::try_new(key.to_be_bytes(), Flags { rounds: Rounds::Custom(0), ..Default::default() }).err(),
+        Some(Error::InvalidRounds(0)));
+    assert_eq!(Tekton128::try_new(key.to_be_bytes(), Flags { rounds: Rounds::Custom(MAX_ROUNDS + 1), ..Default::default() }).err(),
+        Some(Error::InvalidRounds(MAX_ROUNDS + 1)));
+}
+
+#[test]
+fn test_key_schedule_v2(){
+    use crate::imp::{Flags, Rounds};
+    let key: u128 = rand::thread_rng().gen();
+
+    for rounds in [Rounds::FASTER, Rounds::SAFER, Rounds::Custom(12)] {
+        let v1 = Tekton128::new(key.to_be_bytes(), Flags { rounds, schedule: KeySchedule::V1 });
+        let v2 = Tekton128::new(key.to_be_bytes(), Flags { rounds, schedule: KeySchedule::V2 });
+
+        for _ in 0..100 {
+            let p: u128 = rand::thread_rng().gen();
+
+            let mut enc1 = p.to_be_bytes();
+            v1.encrypt(&mut enc1);
+            let mut enc2 = p.to_be_bytes();
+            v2.encrypt(&mut enc2);
+            assert_ne!(enc1, enc2);
+
+            v2.decrypt(&mut enc2);
+            assert_eq!(enc2, p.to_be_bytes());
+        }
+    }
+
+    // every round key has to react to every master key bit
+    let tekton = Tekton128::new(key.to_be_bytes(), Flags { rounds: Rounds::Custom(MAX_ROUNDS), schedule: KeySchedule::V2 });
+    for bit in 0..128 {
+        let flipped = Tekton128::new((key ^ (1 << bit)).to_be_bytes(),
+            Flags { rounds: Rounds::Custom(MAX_ROUNDS), schedule: KeySchedule::V2 });
+
+        for (a, b) in tekton.round_keys().iter().zip(flipped.round_keys()) {
+            let diff: u32 = a.iter().zip(b).map(|(x, y)| (x ^ y).count_ones()).sum();
+            assert!(diff >= 16, "bit {} changes only {} bits of a round key", bit, diff);
+        }
+    }
+
+    let mut enc: [u8; 16] = core::array::from_fn(|i| (0x11 * i) as u8);
+    Tekton128::new(core::array::from_fn(|i| i as u8), Flags { rounds: Rounds::SAFER, schedule: KeySchedule::V2 }).encrypt(&mut enc);
+    assert_eq!(enc, [0xf8, 0x56, 0xac, 0xe4, 0x2a, 0x93, 0xab, 0x8f, 0x3c, 0xff, 0xd8, 0x5d, 0x1b, 0x6e, 0x38, 0x6c]);
+}
+
+#[test]
+fn test_key_hygiene(){
+    use crate::imp::{Flags, Rounds};
+    use core::mem::ManuallyDrop;
+
+    let tekton = Tekton128::new([0xab; 16], Flags { rounds: Rounds::Custom(7), schedule: KeySchedule::V2 });
+    assert_eq!(format!("{:?}", tekton), "Tekton128 { rounds: 7, .. }");
+
+    let cloned = tekton.clone();
+    let p: u128 = rand::thread_rng().gen();
+    let mut enc1 = p.to_be_bytes();
+    tekton.encrypt(&mut enc1);
+    drop(tekton);
+    let mut enc2 = p.to_be_bytes();
+    cloned.encrypt(&mut enc2);
+    assert_eq!(enc1, enc2);
+
+    let mut tekton = ManuallyDrop::new(cloned);
+    assert!(tekton.round_keys().iter().all(|k| *k != [0; 16]));
+    unsafe { ManuallyDrop::drop(&mut tekton) };
+    assert!(tekton.keys.iter().all(|k| *k == [0; 16]));
+}
+
+#[test]
+fn test_encrypt_blocks(){
+    use crate::imp::{Flags, Rounds};
+
+    for rounds in [Rounds::FASTER, Rounds::SAFER, Rounds::Custom(11)] {
+        let tekton = Tekton128::new(rand::thread_rng().gen::<u128>().to_be_bytes(), Flags { rounds, schedule: KeySchedule::V2 });
+
+        for n in 0..40 {
+            let blocks: Vec<[u8; 16]> = (0..n).map(|_| rand::thread_rng().gen::<u128>().to_be_bytes()).collect();
+
+            let mut enc = blocks.clone();
+            tekton.encrypt_blocks(&mut enc);
+
+            for (p, c) in blocks.iter().zip(enc.iter()) {
+                let mut single = *p;
+                tekton.encrypt(&mut single);
+                assert_eq!(&single, c);
+            }
+
+            tekton.decrypt_blocks(&mut enc);
+            assert_eq!(enc, blocks);
+        }
+    }
+}
+
+#[cfg(not(feature = "scalar"))]
+#[test]
+fn test_scalar_matches_simd(){
+    use crate::imp::{Flags, Rounds, scalar};
+
+    let presets = [Rounds::FASTER, Rounds::SAFER];
+    let custom = (1..=MAX_ROUNDS).map(Rounds::Custom);
+
+    for rounds in presets.into_iter().chain(custom) {
+        for schedule in [KeySchedule::V1, KeySchedule::V2] {
+            let tekton = Tekton128::new(rand::thread_rng().gen::<u128>().to_be_bytes(), Flags { rounds, schedule });
+
+            let blocks: Vec<[u8; 16]> = (0..37).map(|_| rand::thread_rng().gen::<u128>().to_be_bytes()).collect();
+
+            let mut simd = blocks.clone();
+            tekton.encrypt_blocks(&mut simd);
+            let mut portable = blocks.clone();
+            scalar::encrypt_blocks(tekton.round_keys(), &mut portable);
+            assert_eq!(simd, portable);
+
+            for (p, c) in blocks.iter().zip(simd.iter()) {
+                let mut single = *p;
+                tekton.encrypt(&mut single);
+                let mut portable = *p;
+                scalar::encrypt_block(tekton.round_keys(), &mut portable);
+                assert_eq!(single, portable);
+                assert_eq!(&single, c);
+
+                scalar::decrypt_block(tekton.round_keys(), &mut portable);
+                assert_eq!(&portable, p);
+            }
+
+            tekton.decrypt_blocks(&mut simd);
+            scalar::decrypt_blocks(tekton.round_keys(), &mut portable);
+            assert_eq!(simd, blocks);
+            assert_eq!(portable, blocks);
+        }
+    }
+}
@@ -1,80 +1,191 @@
+use core::fmt;
 
-use std::simd;
+use cipher::{
+    consts::{U16, U32}, BlockCipher, BlockClosure, BlockDecrypt, BlockEncrypt, BlockSizeUser,
+    Key, KeyInit, KeySizeUser,
+};
 
-#[allow(unused)]
+#[cfg(test)]
 use rand::{Rng};
 
-use super::primitives::*;
-use crate::imp::{Flags, Rounds};
-use std::simd::Simd;
+use zeroize::{Zeroize, ZeroizeOnDrop};
 
+use super::{backend::*, engine, schedule::*};
+use crate::imp::{Error, Flags, KeySchedule, TektonCipher, MAX_ROUNDS};
+
+/// Round keys are wiped when the cipher is dropped and never show up in `Debug`.
+/// The cipher is `Send + Sync`, a single instance can be shared by worker threads.
+#[derive(Clone)]
 pub struct Tekton256 {
-    keys: [Simd<u8, 16>; 8],
-    flags: Flags
+    keys: [[u8; 16]; MAX_ROUNDS],
+    rounds: usize
 }
 
 impl Tekton256 {
 
+    /// Panics if `flags.rounds` is out of range, see `try_new`.
     pub fn new(key: [u8; 32], flags: Flags) -> Tekton256{
+        return <Tekton256 as TektonCipher>::new(key, flags);
+    }
+
+    pub fn try_new(key: [u8; 32], flags: Flags) -> Result<Tekton256, Error>{
+        let range = flags.rounds.key_range(Self::ROUND_KEYS)?;
+
+        let mut tekton = Tekton256 {
+            keys: [[0; 16]; MAX_ROUNDS],
+            rounds: range.len()
+        };
+        let keys = &mut tekton.keys;
 
-        let mut keys: [Simd<u8, 16>; 8] = [simd::u8x16::splat(0); 8];
+        let mut hi: [u8; 16] = [0; 16];
+        hi.copy_from_slice(&key[0..16]);
+        let mut lo: [u8; 16] = [0; 16];
+        lo.copy_from_slice(&key[16..32]);
 
-        for i in 0..8 {
-            let bytes = key.map(|x| (x << i).wrapping_mul(113));
-            let mut hi: [u8;16] = [0;16];
-            hi.copy_from_slice(&bytes[0..16]);
-            let mut lo: [u8; 16] = [0;16];
-            lo.copy_from_slice(&bytes[16..32]);
-            for i in 0..16 {
-                lo[i] ^= hi[i];
+        match flags.schedule {
+            KeySchedule::V1 => {
+                for (ki, i) in keys.iter_mut().zip(range.clone()) {
+                    let (khi, klo) = (v1_round_key(hi, i), v1_round_key(lo, i));
+                    *ki = core::array::from_fn(|j| khi[j] ^ klo[j]);
+                }
+            },
+            KeySchedule::V2 => {
+                // each half's chain is seeded with the other half, run through chain
+                // steps no round key uses, so even a single round key depends on all
+                // 256 bits; the chains then feed alternate round keys
+                let (mut seed_hi, mut seed_lo) = (v2_round_key(lo, MAX_ROUNDS), v2_round_key(hi, MAX_ROUNDS + 1));
+                let mut halves = [
+                    core::array::from_fn(|j| hi[j] ^ seed_hi[j]),
+                    core::array::from_fn(|j| lo[j] ^ seed_lo[j]),
+                ];
+                for i in 0..range.end {
+                    let k = &mut halves[i % 2];
+                    *k = v2_round_key(*k, i);
+                    if i >= range.start {
+                        keys[i - range.start] = *k;
+                    }
+                }
+                for k in halves.iter_mut() {
+                    k.zeroize();
+                }
+                seed_hi.zeroize();
+                seed_lo.zeroize();
             }
-            keys[i] = simd::u8x16::from_array(lo);
         }
 
-        return Tekton256 {
-            keys,
-            flags
-        }
+        hi.zeroize();
+        lo.zeroize();
+
+        return Ok(tekton)
     }
 
     #[inline]
-    pub fn encrypt(&self, payload: &mut [u8; 16]){
+    fn round_keys(&self) -> &[[u8; 16]] {
+        return &self.keys[..self.rounds];
+    }
 
-    
-        let mut state = simd::u8x16::from_array(*payload);
-        
-        if self.flags.rounds == Rounds::SAFER {
-            state = encrypt_round(state, self.keys[0]);
-            state = encrypt_round(state, self.keys[1]);
-        }
-        state = encrypt_round(state, self.keys[2]);
-        state = encrypt_round(state, self.keys[3]);
-        state = encrypt_round(state, self.keys[4]);
-        state = encrypt_round(state, self.keys[5]);
-        state = encrypt_round(state, self.keys[6]);
-        state = encrypt_round(state, self.keys[7]);
-        
-        *payload = *state.as_array();
+    #[inline]
+    pub fn encrypt(&self, payload: &mut [u8; 16]){
+        engine::encrypt_block(self.round_keys(), payload);
     }
 
     #[inline]
     pub fn decrypt(&self, cipher: &mut [u8; 16]){
+        engine::decrypt_block(self.round_keys(), cipher);
+    }
+
+    #[inline]
+    pub fn encrypt_blocks(&self, blocks: &mut [[u8; 16]]){
+        engine::encrypt_blocks(self.round_keys(), blocks);
+    }
 
-        let mut state = simd::u8x16::from_array(*cipher);
-        
-        state = decrypt_round(state, self.keys[7]);
-        state = decrypt_round(state, self.keys[6]);
-        state = decrypt_round(state, self.keys[5]);
-        state = decrypt_round(state, self.keys[4]);
-        state = decrypt_round(state, self.keys[3]);   
-        state = decrypt_round(state, self.keys[2]);
-
-        if self.flags.rounds == Rounds::SAFER {
-            state = decrypt_round(state, self.keys[1]);
-            state = decrypt_round(state, self.keys[0]);
+    #[inline]
+    pub fn decrypt_blocks(&self, blocks: &mut [[u8; 16]]){
+        engine::decrypt_blocks(self.round_keys(), blocks);
+    }
+}
+
+
+impl Drop for Tekton256 {
+    fn drop(&mut self) {
+        for k in self.keys.iter_mut() {
+            k.zeroize();
         }
-        
-        *cipher = *state.as_array();
+    }
+}
+
+impl ZeroizeOnDrop for Tekton256 {}
+
+impl fmt::Debug for Tekton256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tekton256")
+            .field("rounds", &self.rounds)
+            .finish_non_exhaustive()
+    }
+}
+
+
+impl TektonCipher for Tekton256 {
+    type Key = [u8; 32];
+
+    const ROUND_KEYS: usize = 8;
+
+    fn try_new(key: [u8; 32], flags: Flags) -> Result<Tekton256, Error> {
+        Tekton256::try_new(key, flags)
+    }
+
+    fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    #[inline]
+    fn encrypt_block(&self, payload: &mut [u8; 16]) {
+        self.encrypt(payload)
+    }
+
+    #[inline]
+    fn decrypt_block(&self, cipher: &mut [u8; 16]) {
+        self.decrypt(cipher)
+    }
+
+    #[inline]
+    fn encrypt_blocks(&self, blocks: &mut [[u8; 16]]) {
+        self.encrypt_blocks(blocks)
+    }
+
+    #[inline]
+    fn decrypt_blocks(&self, blocks: &mut [[u8; 16]]) {
+        self.decrypt_blocks(blocks)
+    }
+}
+
+
+impl BlockSizeUser for Tekton256 {
+    type BlockSize = U16;
+}
+
+impl KeySizeUser for Tekton256 {
+    type KeySize = U32;
+}
+
+impl BlockCipher for Tekton256 {}
+
+/// Same as `Tekton256::new(key, Flags::v2())`, see `KeyInit` on `Tekton128`.
+impl KeyInit for Tekton256 {
+    fn new(key: &Key<Self>) -> Self {
+        Tekton256::new((*key).into(), Flags::v2())
+    }
+}
+
+impl BlockEncrypt for Tekton256 {
+    fn encrypt_with_backend(&self, f: impl BlockClosure<BlockSize = U16>) {
+        f.call(&mut TektonEnc(self))
+    }
+}
+
+impl BlockDecrypt for Tekton256 {
+    fn decrypt_with_backend(&self, f: impl BlockClosure<BlockSize = U16>) {
+        f.call(&mut TektonDec(self))
     }
 }
 
@@ -108,13 +219,209 @@ fn test_encrypt_decrypt(){
 
     test_in_loop(
         Tekton256::new(key, 
-        Flags {
-            rounds: Rounds::FASTER}));
+        Flags { rounds: Rounds::FASTER, ..Default::default() }));
 
     test_in_loop(
         Tekton256::new(key, 
-        Flags {
-            rounds: Rounds::SAFER}));
+        Flags { rounds: Rounds::SAFER, ..Default::default() }));
 
     
-}
\ No newline at end of file
+}
+#[test]
+fn test_known_answer(){
+    use crate::imp::{Flags, Rounds};
+    let key: [u8; 32] = core::array::from_fn(|i| (7 * i + 3) as u8);
+    let payload: [u8; 16] = core::array::from_fn(|i| (0x11 * i) as u8);
+
+    let mut enc = payload;
+    Tekton256::new(key, Flags { rounds: Rounds::FASTER, ..Default::default() }).encrypt(&mut enc);
+    assert_eq!(enc, [0x5b, 0x5e, 0xe4, 0x47, 0x56, 0x98, 0x3a, 0xbf, 0x4b, 0x78, 0x14, 0x58, 0x65, 0xb0, 0x5e, 0x44]);
+
+    let mut enc = payload;
+    Tekton256::new(key, Flags { rounds: Rounds::SAFER, ..Default::default() }).encrypt(&mut enc);
+    assert_eq!(enc, [0xb9, 0xf3, 0x53, 0x99, 0x88, 0xaa, 0xf8, 0x3d, 0x22, 0xc5, 0xea, 0x81, 0xfd, 0x2a, 0x2a, 0x90]);
+}
+
+#[test]
+fn test_custom_rounds(){
+    use crate::imp::{Flags, Rounds};
+    let mut key: [u8; 32] = [0; 32];
+    rand::thread_rng().fill(&mut key);
+    let p: u128 = rand::thread_rng().gen();
+
+    for n in [1, 4, 12, MAX_ROUNDS] {
+        let tekton = Tekton256::new(key, Flags { rounds: Rounds::Custom(n), ..Default::default() });
+        assert_eq!(tekton.rounds(), n);
+
+        let mut enc = p.to_be_bytes();
+        tekton.encrypt(&mut enc);
+        tekton.decrypt(&mut enc);
+        assert_eq!(enc, p.to_be_bytes());
+    }
+
+    let mut safer = p.to_be_bytes();
+    Tekton256::new(key, Flags { rounds: Rounds::SAFER, ..Default::default() }).encrypt(&mut safer);
+    let mut custom = p.to_be_bytes();
+    Tekton256::new(key, Flags { rounds: Rounds::Custom(8), ..Default::default() }).encrypt(&mut custom);
+    assert_eq!(safer, custom);
+
+    assert_eq!(Tekton256::try_new(key, Flags { rounds: Rounds::Custom(0), ..Default::default() }).err(),
+        Some(Error::InvalidRounds(0)));
+    assert_eq!(Tekton256::try_new(key, Flags { rounds: Rounds::Custom(MAX_ROUNDS + 1), ..Default::default() }).err(),
+        Some(Error::InvalidRounds(MAX_ROUNDS + 1)));
+}
+
+#[test]
+fn test_key_schedule_v2_full_key(){
+    use crate::imp::{Flags, Rounds};
+    let mut key: [u8; 32] = [0; 32];
+    rand::thread_rng().fill(&mut key);
+
+    // swapped halves keep hi ^ lo, and V1 can not tell the two keys apart
+    let mut other = key;
+    other[..16].copy_from_slice(&key[16..]);
+    other[16..].copy_from_slice(&key[..16]);
+
+    // same hi ^ lo as well
+    let mut masked = key;
+    for b in masked.iter_mut() {
+        *b ^= 0x5a;
+    }
+
+    let p: u128 = rand::thread_rng().gen();
+
+    for rounds in [Rounds::FASTER, Rounds::SAFER, Rounds::Custom(12)] {
+        let mut enc1 = p.to_be_bytes();
+        Tekton256::new(key, Flags { rounds, schedule: KeySchedule::V1 }).encrypt(&mut enc1);
+        let mut enc2 = p.to_be_bytes();
+        Tekton256::new(other, Flags { rounds, schedule: KeySchedule::V1 }).encrypt(&mut enc2);
+        assert_eq!(enc1, enc2);
+
+        let tekton = Tekton256::new(key, Flags { rounds, schedule: KeySchedule::V2 });
+        let mut enc1 = p.to_be_bytes();
+        tekton.encrypt(&mut enc1);
+        let mut enc2 = p.to_be_bytes();
+        Tekton256::new(other, Flags { rounds, schedule: KeySchedule::V2 }).encrypt(&mut enc2);
+        assert_ne!(enc1, enc2);
+        let mut enc3 = p.to_be_bytes();
+        Tekton256::new(masked, Flags { rounds, schedule: KeySchedule::V2 }).encrypt(&mut enc3);
+        assert_ne!(enc1, enc3);
+
+        tekton.decrypt(&mut enc1);
+        assert_eq!(enc1, p.to_be_bytes());
+    }
+
+    // every key bit, from either half, has to reach the ciphertext, even with a single round key
+    for rounds in [Rounds::SAFER, Rounds::Custom(1)] {
+        let tekton = Tekton256::new(key, Flags { rounds, schedule: KeySchedule::V2 });
+        let mut enc = p.to_be_bytes();
+        tekton.encrypt(&mut enc);
+        for bit in 0..256 {
+            let mut flipped = key;
+            flipped[bit / 8] ^= 1 << (bit % 8);
+
+            let mut enc_flipped = p.to_be_bytes();
+            Tekton256::new(flipped, Flags { rounds, schedule: KeySchedule::V2 }).encrypt(&mut enc_flipped);
+            assert_ne!(enc, enc_flipped);
+        }
+    }
+    let mut lo_flipped = key;
+    lo_flipped[16] ^= 0xff;
+    let mut enc1 = p.to_be_bytes();
+    Tekton256::new(key, Flags { rounds: Rounds::Custom(1), schedule: KeySchedule::V2 }).encrypt(&mut enc1);
+    let mut enc2 = p.to_be_bytes();
+    Tekton256::new(lo_flipped, Flags { rounds: Rounds::Custom(1), schedule: KeySchedule::V2 }).encrypt(&mut enc2);
+    assert_ne!(enc1, enc2);
+
+    let mut enc: [u8; 16] = core::array::from_fn(|i| (0x11 * i) as u8);
+    Tekton256::new(core::array::from_fn(|i| (7 * i + 3) as u8), Flags { rounds: Rounds::SAFER, schedule: KeySchedule::V2 }).encrypt(&mut enc);
+    assert_eq!(enc, [0xdc, 0xe3, 0x5d, 0x14, 0x59, 0xae, 0xb3, 0xfb, 0x33, 0x77, 0xf3, 0x27, 0xd5, 0xb2, 0x8e, 0x4d]);
+}
+
+#[test]
+fn test_key_hygiene(){
+    use crate::imp::{Flags, Rounds};
+    use core::mem::ManuallyDrop;
+
+    let tekton = Tekton256::new(core::array::from_fn(|i| (7 * i + 3) as u8), Flags { rounds: Rounds::Custom(7), schedule: KeySchedule::V2 });
+    assert_eq!(format!("{:?}", tekton), "Tekton256 { rounds: 7, .. }");
+
+    let cloned = tekton.clone();
+    let p: u128 = rand::thread_rng().gen();
+    let mut enc1 = p.to_be_bytes();
+    tekton.encrypt(&mut enc1);
+    drop(tekton);
+    let mut enc2 = p.to_be_bytes();
+    cloned.encrypt(&mut enc2);
+    assert_eq!(enc1, enc2);
+
+    let mut tekton = ManuallyDrop::new(cloned);
+    assert!(tekton.round_keys().iter().all(|k| *k != [0; 16]));
+    unsafe { ManuallyDrop::drop(&mut tekton) };
+    assert!(tekton.keys.iter().all(|k| *k == [0; 16]));
+}
+
+#[test]
+fn test_encrypt_blocks(){
+    use crate::imp::{Flags, Rounds};
+
+    for rounds in [Rounds::FASTER, Rounds::SAFER, Rounds::Custom(11)] {
+        let tekton = Tekton256::new(rand::thread_rng().gen::<[u8; 32]>(), Flags { rounds, schedule: KeySchedule::V2 });
+
+        for n in 0..40 {
+            let blocks: Vec<[u8; 16]> = (0..n).map(|_| rand::thread_rng().gen::<u128>().to_be_bytes()).collect();
+
+            let mut enc = blocks.clone();
+            tekton.encrypt_blocks(&mut enc);
+
+            for (p, c) in blocks.iter().zip(enc.iter()) {
+                let mut single = *p;
+                tekton.encrypt(&mut single);
+                assert_eq!(&single, c);
+            }
+
+            tekton.decrypt_blocks(&mut enc);
+            assert_eq!(enc, blocks);
+        }
+    }
+}
+
+#[cfg(not(feature = "scalar"))]
+#[test]
+fn test_scalar_matches_simd(){
+    use crate::imp::{Flags, Rounds, scalar};
+
+    let presets = [Rounds::FASTER, Rounds::SAFER];
+    let custom = (1..=MAX_ROUNDS).map(Rounds::Custom);
+
+    for rounds in presets.into_iter().chain(custom) {
+        for schedule in [KeySchedule::V1, KeySchedule::V2] {
+            let tekton = Tekton256::new(rand::thread_rng().gen::<[u8; 32]>(), Flags { rounds, schedule });
+
+            let blocks: Vec<[u8; 16]> = (0..37).map(|_| rand::thread_rng().gen::<u128>().to_be_bytes()).collect();
+
+            let mut simd = blocks.clone();
+            tekton.encrypt_blocks(&mut simd);
+            let mut portable = blocks.clone();
+            scalar::encrypt_blocks(tekton.round_keys(), &mut portable);
+            assert_eq!(simd, portable);
+
+            for (p, c) in blocks.iter().zip(simd.iter()) {
+                let mut single = *p;
+                tekton.encrypt(&mut single);
+                let mut portable = *p;
+                scalar::encrypt_block(tekton.round_keys(), &mut portable);
+                assert_eq!(single, portable);
+                assert_eq!(&single, c);
+
+                scalar::decrypt_block(tekton.round_keys(), &mut portable);
+                assert_eq!(&portable, p);
+            }
+
+            tekton.decrypt_blocks(&mut simd);
+            scalar::decrypt_blocks(tekton.round_keys(), &mut portable);
+            assert_eq!(simd, blocks);
+            assert_eq!(portable, blocks);
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use cipher::{
+    consts::U16, inout::InOut, Block, BlockBackend, BlockSizeUser, ParBlocks,
+    ParBlocksSizeUser,
+};
+
+use super::TektonCipher;
+
+
+pub(crate) struct TektonEnc<'a, C: TektonCipher>(pub(crate) &'a C);
+
+impl<C: TektonCipher> BlockSizeUser for TektonEnc<'_, C> {
+    type BlockSize = U16;
+}
+
+impl<C: TektonCipher> ParBlocksSizeUser for TektonEnc<'_, C> {
+    type ParBlocksSize = U16;
+}
+
+impl<C: TektonCipher> BlockBackend for TektonEnc<'_, C> {
+    #[inline]
+    fn proc_block(&mut self, mut block: InOut<'_, '_, Block<Self>>) {
+        let mut payload: [u8; 16] = (*block.get_in()).into();
+        self.0.encrypt_block(&mut payload);
+        *block.get_out() = payload.into();
+    }
+
+    #[inline]
+    fn proc_par_blocks(&mut self, mut blocks: InOut<'_, '_, ParBlocks<Self>>) {
+        let mut payload: [[u8; 16]; 16] = [[0; 16]; 16];
+        for (p, b) in payload.iter_mut().zip(blocks.get_in().iter()) {
+            *p = (*b).into();
+        }
+        self.0.encrypt_blocks(&mut payload);
+        for (b, p) in blocks.get_out().iter_mut().zip(payload) {
+            *b = p.into();
+        }
+    }
+}
+
+
+pub(crate) struct TektonDec<'a, C: TektonCipher>(pub(crate) &'a C);
+
+impl<C: TektonCipher> BlockSizeUser for TektonDec<'_, C> {
+    type BlockSize = U16;
+}
+
+impl<C: TektonCipher> ParBlocksSizeUser for TektonDec<'_, C> {
+    type ParBlocksSize = U16;
+}
+
+impl<C: TektonCipher> BlockBackend for TektonDec<'_, C> {
+    #[inline]
+    fn proc_block(&mut self, mut block: InOut<'_, '_, Block<Self>>) {
+        let mut cipher: [u8; 16] = (*block.get_in()).into();
+        self.0.decrypt_block(&mut cipher);
+        *block.get_out() = cipher.into();
+    }
+
+    #[inline]
+    fn proc_par_blocks(&mut self, mut blocks: InOut<'_, '_, ParBlocks<Self>>) {
+        let mut payload: [[u8; 16]; 16] = [[0; 16]; 16];
+        for (p, b) in payload.iter_mut().zip(blocks.get_in().iter()) {
+            *p = (*b).into();
+        }
+        self.0.decrypt_blocks(&mut payload);
+        for (b, p) in blocks.get_out().iter_mut().zip(payload) {
+            *b = p.into();
+        }
+    }
+}
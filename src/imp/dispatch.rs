@@ -0,0 +1,89 @@
+use core::sync::atomic::{AtomicU8, Ordering};
+
+use super::Error;
+
+
+/// Instruction sets the bulk paths (`encrypt_blocks`, `decrypt_blocks`) are compiled for.
+/// All of them produce identical output. The `scalar` round function has a single
+/// path, with that feature only `Baseline` is supported.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Backend {
+    /// Whatever the crate was built for.
+    Baseline,
+    Avx2,
+    /// AVX-512 F + BW + DQ, the latter provides native u64 multiplies.
+    Avx512,
+}
+
+const AUTO: u8 = u8::MAX;
+
+static SELECTED: AtomicU8 = AtomicU8::new(AUTO);
+
+impl Backend {
+
+    pub const ALL: [Backend; 3] = [Backend::Baseline, Backend::Avx2, Backend::Avx512];
+
+    #[cfg(all(target_arch = "x86_64", feature = "std", not(feature = "scalar")))]
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Backend::Baseline => true,
+            Backend::Avx2 => std::is_x86_feature_detected!("avx2"),
+            Backend::Avx512 => std::is_x86_feature_detected!("avx512f")
+                && std::is_x86_feature_detected!("avx512bw")
+                && std::is_x86_feature_detected!("avx512dq"),
+        }
+    }
+
+    // without std there is no runtime detection, only what the target enables
+    #[cfg(all(target_arch = "x86_64", not(feature = "std"), not(feature = "scalar")))]
+    pub fn is_supported(&self) -> bool {
+        match self {
+            Backend::Baseline => true,
+            Backend::Avx2 => cfg!(target_feature = "avx2"),
+            Backend::Avx512 => cfg!(all(target_feature = "avx512f", target_feature = "avx512bw", target_feature = "avx512dq")),
+        }
+    }
+
+    #[cfg(any(not(target_arch = "x86_64"), feature = "scalar"))]
+    pub fn is_supported(&self) -> bool {
+        *self == Backend::Baseline
+    }
+
+    /// The fastest backend this CPU supports.
+    pub fn detect() -> Backend {
+        [Backend::Avx512, Backend::Avx2]
+            .into_iter()
+            .find(|b| b.is_supported())
+            .unwrap_or(Backend::Baseline)
+    }
+}
+
+
+/// Backend used by the bulk paths, detected on first use unless forced.
+#[inline]
+pub fn backend() -> Backend {
+    match SELECTED.load(Ordering::Relaxed) {
+        AUTO => {
+            let detected = Backend::detect();
+            let _ = SELECTED.compare_exchange(AUTO, detected as u8, Ordering::Relaxed, Ordering::Relaxed);
+            backend()
+        },
+        b => Backend::ALL[b as usize],
+    }
+}
+
+/// Forces every cipher in the process onto `backend`, `None` goes back to detection.
+/// Meant for tests and benchmarks comparing the backends.
+pub fn force_backend(backend: Option<Backend>) -> Result<(), Error> {
+    match backend {
+        Some(b) if !b.is_supported() => Err(Error::UnsupportedBackend(b)),
+        Some(b) => {
+            SELECTED.store(b as u8, Ordering::Relaxed);
+            Ok(())
+        },
+        None => {
+            SELECTED.store(AUTO, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+}
@@ -1,29 +1,31 @@
-use std::simd::{Simd};
-use std::simd;
+use core::simd::{Simd, Swizzle};
+use core::simd;
+
+use super::dispatch;
 
 
 #[inline]
 pub fn rotate(a: Simd<u8, 16>) -> Simd<u8, 16>{
-    return a.rotate_lanes_left::<7>();
+    return a.rotate_elements_left::<7>();
 }
 
 #[inline]
 pub fn inverse_rotate(a: Simd<u8, 16>) -> Simd<u8, 16>{
-    return a.rotate_lanes_right::<7>();
+    return a.rotate_elements_right::<7>();
 }
 
 
 #[inline]
 pub fn expansion(a: Simd<u8, 16>) -> Simd<u8, 16> {
     let b: Simd<u64, 2> = unsafe {
-        std::mem::transmute::<Simd<u8, 16>, Simd<u64, 2>>(a)
+        core::mem::transmute::<Simd<u8, 16>, Simd<u64, 2>>(a)
     };
 
     let b = b * E;
     
 
     return unsafe {
-        std::mem::transmute::<Simd<u64, 2>, Simd<u8, 16>>(b)
+        core::mem::transmute::<Simd<u64, 2>, Simd<u8, 16>>(b)
     };
 }
 
@@ -32,13 +34,13 @@ pub fn expansion(a: Simd<u8, 16>) -> Simd<u8, 16> {
 #[inline]
 pub fn inv_expansion(a: Simd<u8, 16>) -> Simd<u8, 16> {
     let b: Simd<u64, 2> = unsafe {
-        std::mem::transmute::<Simd<u8, 16>, Simd<u64, 2>>(a)
+        core::mem::transmute::<Simd<u8, 16>, Simd<u64, 2>>(a)
     };
 
     let b = b * INV_E;
 
     return unsafe {
-        std::mem::transmute::<Simd<u64, 2>, Simd<u8, 16>>(b)
+        core::mem::transmute::<Simd<u64, 2>, Simd<u8, 16>>(b)
     };
 }
 
@@ -76,4 +78,321 @@ pub fn decrypt_round(state: Simd<u8, 16>, key: Simd<u8, 16>) -> Simd<u8, 16>{
     s = inv_expansion(s);
     s ^= key;
     s
-}
\ No newline at end of file
+}
+
+#[inline]
+pub fn encrypt_rounds(state: Simd<u8, 16>, keys: &[[u8; 16]]) -> Simd<u8, 16>{
+    let mut s = state;
+    for key in keys {
+        s = encrypt_round(s, simd::u8x16::from_array(*key));
+    }
+    s
+}
+
+#[inline]
+pub fn decrypt_rounds(state: Simd<u8, 16>, keys: &[[u8; 16]]) -> Simd<u8, 16>{
+    let mut s = state;
+    for key in keys.iter().rev() {
+        s = decrypt_round(s, simd::u8x16::from_array(*key));
+    }
+    s
+}
+
+#[inline]
+pub fn encrypt_block(keys: &[[u8; 16]], block: &mut [u8; 16]) {
+    *block = *encrypt_rounds(simd::u8x16::from_array(*block), keys).as_array();
+}
+
+#[inline]
+pub fn decrypt_block(keys: &[[u8; 16]], block: &mut [u8; 16]) {
+    *block = *decrypt_rounds(simd::u8x16::from_array(*block), keys).as_array();
+}
+
+
+
+
+// Wide variants of the round function, processing 2 or 4 independent blocks
+// side by side in u8x32/u8x64 registers. Lane `i` of block `b` sits at `16 * b + i`.
+
+struct BlockRotate<const N: usize, const R: usize>;
+
+impl<const N: usize, const R: usize> Swizzle<N> for BlockRotate<N, R> {
+    const INDEX: [usize; N] = {
+        let mut index = [0; N];
+        let mut i = 0;
+        while i < N {
+            index[i] = (i / 16) * 16 + (i % 16 + R) % 16;
+            i += 1;
+        }
+        index
+    };
+}
+
+struct Broadcast<const N: usize>;
+
+impl<const N: usize> Swizzle<N> for Broadcast<N> {
+    const INDEX: [usize; N] = {
+        let mut index = [0; N];
+        let mut i = 0;
+        while i < N {
+            index[i] = i % 16;
+            i += 1;
+        }
+        index
+    };
+}
+
+
+#[inline(always)]
+pub fn rotate_wide<const N: usize>(a: Simd<u8, N>) -> Simd<u8, N> {
+    BlockRotate::<N, 7>::swizzle(a)
+}
+
+#[inline(always)]
+pub fn inverse_rotate_wide<const N: usize>(a: Simd<u8, N>) -> Simd<u8, N> {
+    BlockRotate::<N, 9>::swizzle(a)
+}
+
+#[inline(always)]
+pub fn broadcast<const N: usize>(key: [u8; 16]) -> Simd<u8, N> {
+    Broadcast::<N>::swizzle(simd::u8x16::from_array(key))
+}
+
+
+#[inline(always)]
+pub fn expansion_x2(a: Simd<u8, 32>) -> Simd<u8, 32> {
+    let b = unsafe { core::mem::transmute::<Simd<u8, 32>, Simd<u64, 4>>(a) };
+    let b = b * Simd::splat(E[0]);
+    unsafe { core::mem::transmute::<Simd<u64, 4>, Simd<u8, 32>>(b) }
+}
+
+#[inline(always)]
+pub fn inv_expansion_x2(a: Simd<u8, 32>) -> Simd<u8, 32> {
+    let b = unsafe { core::mem::transmute::<Simd<u8, 32>, Simd<u64, 4>>(a) };
+    let b = b * Simd::splat(INV_E[0]);
+    unsafe { core::mem::transmute::<Simd<u64, 4>, Simd<u8, 32>>(b) }
+}
+
+#[inline(always)]
+pub fn expansion_x4(a: Simd<u8, 64>) -> Simd<u8, 64> {
+    let b = unsafe { core::mem::transmute::<Simd<u8, 64>, Simd<u64, 8>>(a) };
+    let b = b * Simd::splat(E[0]);
+    unsafe { core::mem::transmute::<Simd<u64, 8>, Simd<u8, 64>>(b) }
+}
+
+#[inline(always)]
+pub fn inv_expansion_x4(a: Simd<u8, 64>) -> Simd<u8, 64> {
+    let b = unsafe { core::mem::transmute::<Simd<u8, 64>, Simd<u64, 8>>(a) };
+    let b = b * Simd::splat(INV_E[0]);
+    unsafe { core::mem::transmute::<Simd<u64, 8>, Simd<u8, 64>>(b) }
+}
+
+
+#[inline(always)]
+pub fn encrypt_round_x2(state: Simd<u8, 32>, key: Simd<u8, 32>) -> Simd<u8, 32>{
+    let mut s = state ^ key;
+    s = expansion_x2(s);
+    s *= Simd::splat(S[0]);
+    rotate_wide(s)
+}
+
+#[inline(always)]
+pub fn decrypt_round_x2(state: Simd<u8, 32>, key: Simd<u8, 32>) -> Simd<u8, 32>{
+    let mut s = inverse_rotate_wide(state);
+    s *= Simd::splat(INV_S[0]);
+    s = inv_expansion_x2(s);
+    s ^ key
+}
+
+#[inline(always)]
+pub fn encrypt_round_x4(state: Simd<u8, 64>, key: Simd<u8, 64>) -> Simd<u8, 64>{
+    let mut s = state ^ key;
+    s = expansion_x4(s);
+    s *= Simd::splat(S[0]);
+    rotate_wide(s)
+}
+
+#[inline(always)]
+pub fn decrypt_round_x4(state: Simd<u8, 64>, key: Simd<u8, 64>) -> Simd<u8, 64>{
+    let mut s = inverse_rotate_wide(state);
+    s *= Simd::splat(INV_S[0]);
+    s = inv_expansion_x4(s);
+    s ^ key
+}
+
+
+/// Number of u8x64 registers kept in flight by the interleaved pipeline.
+pub const PIPELINE_DEPTH: usize = 4;
+
+/// Blocks consumed by one pass of the interleaved pipeline.
+pub const PIPELINE_BLOCKS: usize = 4 * PIPELINE_DEPTH;
+
+/// Round `r` of every register is issued before round `r + 1` of any of them,
+/// so the multiplies of independent blocks overlap instead of waiting on each other.
+#[inline(always)]
+pub fn encrypt_interleaved(keys: &[[u8; 16]], blocks: &mut [[u8; 16]; PIPELINE_BLOCKS]) {
+    let mut s: [Simd<u8, 64>; PIPELINE_DEPTH] = core::array::from_fn(|i| {
+        Simd::from_slice(blocks[4 * i..4 * i + 4].as_flattened())
+    });
+    for key in keys {
+        let k = broadcast(*key);
+        for si in s.iter_mut() {
+            *si = encrypt_round_x4(*si, k);
+        }
+    }
+    for (i, si) in s.iter().enumerate() {
+        si.copy_to_slice(blocks[4 * i..4 * i + 4].as_flattened_mut());
+    }
+}
+
+#[inline(always)]
+pub fn decrypt_interleaved(keys: &[[u8; 16]], blocks: &mut [[u8; 16]; PIPELINE_BLOCKS]) {
+    let mut s: [Simd<u8, 64>; PIPELINE_DEPTH] = core::array::from_fn(|i| {
+        Simd::from_slice(blocks[4 * i..4 * i + 4].as_flattened())
+    });
+    for key in keys.iter().rev() {
+        let k = broadcast(*key);
+        for si in s.iter_mut() {
+            *si = decrypt_round_x4(*si, k);
+        }
+    }
+    for (i, si) in s.iter().enumerate() {
+        si.copy_to_slice(blocks[4 * i..4 * i + 4].as_flattened_mut());
+    }
+}
+
+
+/// Encrypts every block with the given round keys, on the backend picked by `dispatch`.
+pub fn encrypt_blocks(keys: &[[u8; 16]], blocks: &mut [[u8; 16]]) {
+    match dispatch::backend() {
+        #[cfg(target_arch = "x86_64")]
+        dispatch::Backend::Avx512 => unsafe { x86::encrypt_blocks_avx512(keys, blocks) },
+        #[cfg(target_arch = "x86_64")]
+        dispatch::Backend::Avx2 => unsafe { x86::encrypt_blocks_avx2(keys, blocks) },
+        _ => encrypt_blocks_inline(keys, blocks),
+    }
+}
+
+/// Inverse of `encrypt_blocks`.
+pub fn decrypt_blocks(keys: &[[u8; 16]], blocks: &mut [[u8; 16]]) {
+    match dispatch::backend() {
+        #[cfg(target_arch = "x86_64")]
+        dispatch::Backend::Avx512 => unsafe { x86::decrypt_blocks_avx512(keys, blocks) },
+        #[cfg(target_arch = "x86_64")]
+        dispatch::Backend::Avx2 => unsafe { x86::decrypt_blocks_avx2(keys, blocks) },
+        _ => decrypt_blocks_inline(keys, blocks),
+    }
+}
+
+
+// Copies of the bulk path compiled with wider registers enabled. The bodies are
+// inlined, so the u64 multiplies and byte multiplies by `S` use AVX2/AVX-512 forms.
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::{decrypt_blocks_inline, encrypt_blocks_inline};
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn encrypt_blocks_avx2(keys: &[[u8; 16]], blocks: &mut [[u8; 16]]) {
+        encrypt_blocks_inline(keys, blocks)
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn decrypt_blocks_avx2(keys: &[[u8; 16]], blocks: &mut [[u8; 16]]) {
+        decrypt_blocks_inline(keys, blocks)
+    }
+
+    #[target_feature(enable = "avx512f,avx512bw,avx512dq")]
+    pub unsafe fn encrypt_blocks_avx512(keys: &[[u8; 16]], blocks: &mut [[u8; 16]]) {
+        encrypt_blocks_inline(keys, blocks)
+    }
+
+    #[target_feature(enable = "avx512f,avx512bw,avx512dq")]
+    pub unsafe fn decrypt_blocks_avx512(keys: &[[u8; 16]], blocks: &mut [[u8; 16]]) {
+        decrypt_blocks_inline(keys, blocks)
+    }
+}
+
+
+/// Full pipeline passes go through `encrypt_interleaved`, the rest four blocks
+/// per u8x64 register, then two, then one.
+#[inline(always)]
+fn encrypt_blocks_inline(keys: &[[u8; 16]], blocks: &mut [[u8; 16]]) {
+    let (passes, rest) = blocks.as_chunks_mut::<PIPELINE_BLOCKS>();
+    for pass in passes {
+        encrypt_interleaved(keys, pass);
+    }
+
+    let mut chunks = rest.chunks_exact_mut(4);
+    for chunk in &mut chunks {
+        let mut s = Simd::<u8, 64>::from_slice(chunk.as_flattened());
+        for key in keys {
+            s = encrypt_round_x4(s, broadcast(*key));
+        }
+        s.copy_to_slice(chunk.as_flattened_mut());
+    }
+
+    let mut chunks = chunks.into_remainder().chunks_exact_mut(2);
+    for chunk in &mut chunks {
+        let mut s = Simd::<u8, 32>::from_slice(chunk.as_flattened());
+        for key in keys {
+            s = encrypt_round_x2(s, broadcast(*key));
+        }
+        s.copy_to_slice(chunk.as_flattened_mut());
+    }
+
+    for block in chunks.into_remainder() {
+        encrypt_block(keys, block);
+    }
+}
+
+#[inline(always)]
+fn decrypt_blocks_inline(keys: &[[u8; 16]], blocks: &mut [[u8; 16]]) {
+    let (passes, rest) = blocks.as_chunks_mut::<PIPELINE_BLOCKS>();
+    for pass in passes {
+        decrypt_interleaved(keys, pass);
+    }
+
+    let mut chunks = rest.chunks_exact_mut(4);
+    for chunk in &mut chunks {
+        let mut s = Simd::<u8, 64>::from_slice(chunk.as_flattened());
+        for key in keys.iter().rev() {
+            s = decrypt_round_x4(s, broadcast(*key));
+        }
+        s.copy_to_slice(chunk.as_flattened_mut());
+    }
+
+    let mut chunks = chunks.into_remainder().chunks_exact_mut(2);
+    for chunk in &mut chunks {
+        let mut s = Simd::<u8, 32>::from_slice(chunk.as_flattened());
+        for key in keys.iter().rev() {
+            s = decrypt_round_x2(s, broadcast(*key));
+        }
+        s.copy_to_slice(chunk.as_flattened_mut());
+    }
+
+    for block in chunks.into_remainder() {
+        decrypt_block(keys, block);
+    }
+}
+
+
+#[test]
+fn test_wide_rounds_match_single(){
+    use rand::Rng;
+
+    let mut blocks: [[u8; 16]; 4] = [[0; 16]; 4];
+    for b in blocks.iter_mut() {
+        rand::thread_rng().fill(b);
+    }
+    let key: [u8; 16] = rand::thread_rng().gen();
+
+    let single = blocks.map(|b| encrypt_round(simd::u8x16::from_array(b), simd::u8x16::from_array(key)).to_array());
+
+    let x4 = encrypt_round_x4(Simd::from_slice(blocks.as_flattened()), broadcast(key));
+    assert_eq!(x4.as_array(), single.as_flattened());
+    assert_eq!(decrypt_round_x4(x4, broadcast(key)).as_array(), blocks.as_flattened());
+
+    let x2 = encrypt_round_x2(Simd::from_slice(blocks[..2].as_flattened()), broadcast(key));
+    assert_eq!(x2.as_array(), single[..2].as_flattened());
+    assert_eq!(decrypt_round_x2(x2, broadcast(key)).as_array(), blocks[..2].as_flattened());
+}
@@ -0,0 +1,117 @@
+// Portable u64/u128 implementation of the round function, bit-identical to the
+// SIMD one in `primitives`. Used instead of it with the `scalar` feature, and by
+// the key schedule either way.
+
+const S: u8 = 191;
+const INV_S: u8 = 63;
+
+const E: u64 = 0b01001111_01001111_01001111_01001111_01001111_01001111_01001111_01001111;
+const INV_E: u64 = 1167515447703136175;
+
+const EVEN_BYTES: u64 = 0x00ff_00ff_00ff_00ff;
+
+
+/// Multiplies every byte of `a` by `m` mod 256. Products of two bytes fit in
+/// 16 bits, so even and odd bytes are multiplied in place without carries leaking.
+#[inline]
+fn mul_bytes(a: u64, m: u8) -> u64 {
+    let even = ((a & EVEN_BYTES).wrapping_mul(m as u64)) & EVEN_BYTES;
+    let odd = (((a >> 8) & EVEN_BYTES).wrapping_mul(m as u64)) & EVEN_BYTES;
+    even | (odd << 8)
+}
+
+#[inline]
+fn to_lanes(a: [u8; 16]) -> [u64; 2] {
+    let (lo, hi) = a.split_at(8);
+    [u64::from_ne_bytes(lo.try_into().unwrap()), u64::from_ne_bytes(hi.try_into().unwrap())]
+}
+
+#[inline]
+fn from_lanes(a: [u64; 2]) -> [u8; 16] {
+    let mut bytes = [0; 16];
+    bytes[..8].copy_from_slice(&a[0].to_ne_bytes());
+    bytes[8..].copy_from_slice(&a[1].to_ne_bytes());
+    bytes
+}
+
+
+/// Same as `rotate_elements_left::<7>` on a u8x16.
+#[inline]
+pub fn rotate(a: [u8; 16]) -> [u8; 16] {
+    u128::from_le_bytes(a).rotate_right(56).to_le_bytes()
+}
+
+#[inline]
+pub fn inverse_rotate(a: [u8; 16]) -> [u8; 16] {
+    u128::from_le_bytes(a).rotate_left(56).to_le_bytes()
+}
+
+
+#[inline]
+pub fn encrypt_round(state: [u8; 16], key: [u8; 16]) -> [u8; 16] {
+    let s = to_lanes(state);
+    let k = to_lanes(key);
+
+    let s = [0, 1].map(|i| mul_bytes((s[i] ^ k[i]).wrapping_mul(E), S));
+
+    rotate(from_lanes(s))
+}
+
+#[inline]
+pub fn decrypt_round(state: [u8; 16], key: [u8; 16]) -> [u8; 16] {
+    let s = to_lanes(inverse_rotate(state));
+    let k = to_lanes(key);
+
+    let s = [0, 1].map(|i| mul_bytes(s[i], INV_S).wrapping_mul(INV_E) ^ k[i]);
+
+    from_lanes(s)
+}
+
+
+#[inline]
+pub fn encrypt_block(keys: &[[u8; 16]], block: &mut [u8; 16]) {
+    for key in keys {
+        *block = encrypt_round(*block, *key);
+    }
+}
+
+#[inline]
+pub fn decrypt_block(keys: &[[u8; 16]], block: &mut [u8; 16]) {
+    for key in keys.iter().rev() {
+        *block = decrypt_round(*block, *key);
+    }
+}
+
+pub fn encrypt_blocks(keys: &[[u8; 16]], blocks: &mut [[u8; 16]]) {
+    for block in blocks {
+        encrypt_block(keys, block);
+    }
+}
+
+pub fn decrypt_blocks(keys: &[[u8; 16]], blocks: &mut [[u8; 16]]) {
+    for block in blocks {
+        decrypt_block(keys, block);
+    }
+}
+
+
+#[cfg(not(feature = "scalar"))]
+#[test]
+fn test_matches_simd_round(){
+    use rand::Rng;
+    use core::simd::u8x16;
+    use super::primitives;
+
+    for _ in 0..10_000 {
+        let state: [u8; 16] = rand::thread_rng().gen();
+        let key: [u8; 16] = rand::thread_rng().gen();
+
+        let simd = primitives::encrypt_round(u8x16::from_array(state), u8x16::from_array(key));
+        assert_eq!(encrypt_round(state, key), simd.to_array());
+
+        let simd = primitives::decrypt_round(u8x16::from_array(state), u8x16::from_array(key));
+        assert_eq!(decrypt_round(state, key), simd.to_array());
+
+        assert_eq!(decrypt_round(encrypt_round(state, key), key), state);
+    }
+}
@@ -0,0 +1,30 @@
+use super::scalar::encrypt_round;
+
+
+/// Number of `encrypt_round` applications between consecutive `V2` round keys,
+/// three rounds are enough for every output bit to depend on every input bit.
+const V2_STEPS: usize = 3;
+
+
+#[inline]
+pub fn v1_round_key(key: [u8; 16], i: usize) -> [u8; 16] {
+    key.map(|x| (x.rotate_left((i / 8) as u32) << (i % 8)).wrapping_mul(113))
+}
+
+
+pub fn round_constant(i: usize) -> [u8; 16] {
+    core::array::from_fn(|j| {
+        (((16 * i + j + 1) as u32).wrapping_mul(0x9E3779B9) >> 24) as u8
+    })
+}
+
+
+/// Derives `V2` round key `i` from round key `i - 1`, the master key stands in for round key `-1`.
+#[inline]
+pub fn v2_round_key(prev: [u8; 16], i: usize) -> [u8; 16] {
+    let mut s = prev;
+    for step in 0..V2_STEPS {
+        s = encrypt_round(s, round_constant(V2_STEPS * i + step));
+    }
+    s
+}
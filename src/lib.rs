@@ -1,3 +1,9 @@
-#![feature(portable_simd)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(not(feature = "scalar"), feature(portable_simd))]
 
-pub mod imp;
\ No newline at end of file
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod imp;
+
+pub mod modes;
\ No newline at end of file
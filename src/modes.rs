@@ -0,0 +1,144 @@
+use core::fmt;
+
+use cipher::{consts::U16, inout::InOutBuf, generic_array::GenericArray};
+
+pub mod padding;
+
+pub mod ecb;
+
+pub mod cbc;
+
+pub mod ctr;
+
+pub mod cfb;
+
+pub mod ofb;
+
+pub mod xts;
+
+pub mod gcm;
+
+pub mod cmac;
+
+pub mod pmac;
+
+pub mod siv;
+
+pub mod ocb3;
+
+pub mod eax;
+
+pub mod ccm;
+
+pub mod kw;
+
+pub mod hctr2;
+
+#[cfg(feature = "alloc")]
+pub mod fpe;
+
+
+/// Block size of every cipher the modes accept.
+pub const BLOCK_SIZE: usize = 16;
+
+/// Blocks the modes pass to the cipher's batched path at a time, matches the
+/// width of its parallel backend.
+pub(crate) const BATCH: usize = 16;
+
+type Block = GenericArray<u8, U16>;
+
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Error {
+    /// The input is not a whole number of blocks, or the output buffer is too small.
+    InvalidLength,
+    /// Decrypted data does not end in valid PKCS#7 padding.
+    InvalidPadding,
+    /// Key material a mode refuses, like equal XTS key halves.
+    InvalidKey,
+    /// An unwrapped key fails the integrity check of the key wrap.
+    IntegrityCheck,
+    /// A radix or alphabet a format-preserving mode cannot use.
+    InvalidRadix,
+    /// A numeral or character outside the radix or alphabet.
+    InvalidNumeral,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidLength => write!(f, "invalid data length"),
+            Error::InvalidPadding => write!(f, "invalid PKCS#7 padding"),
+            Error::InvalidKey => write!(f, "invalid key"),
+            Error::IntegrityCheck => write!(f, "key wrap integrity check failed"),
+            Error::InvalidRadix => write!(f, "invalid radix or alphabet"),
+            Error::InvalidNumeral => write!(f, "numeral outside the radix or alphabet"),
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+
+/// Views `data` as blocks, without copying.
+pub(crate) fn blocks_mut(data: &mut [u8]) -> Result<&mut [Block], Error> {
+    let (blocks, tail) = InOutBuf::from(data).into_chunks::<U16>();
+    if !tail.is_empty() {
+        return Err(Error::InvalidLength);
+    }
+    Ok(blocks.into_out())
+}
+
+/// Doubling in GF(2^128) modulo x^128 + x^7 + x^2 + x + 1, the byte order
+/// is up to the caller.
+#[inline]
+pub(crate) fn dbl(x: u128) -> u128 {
+    (x << 1) ^ ((x >> 127) * 0x87)
+}
+
+#[inline]
+pub(crate) fn xor(a: &mut [u8], b: &[u8]) {
+    for (x, y) in a.iter_mut().zip(b) {
+        *x ^= y;
+    }
+}
+
+
+/// Round trips through `aead` for lengths around a batch, flipping a byte of
+/// the ciphertext, tag, associated data or nonce must fail, and a failed
+/// check must leave the ciphertext in the buffer.
+#[cfg(all(test, feature = "alloc"))]
+pub(crate) fn check_aead<A: aead::AeadInPlace>(aead: &A) {
+    use aead::{generic_array::typenum::Unsigned, Aead, Nonce, Payload, Tag};
+    use rand::Rng;
+
+    let mut nonce = Nonce::<A>::default();
+    rand::thread_rng().fill(nonce.as_mut_slice());
+
+    for len in [0, 1, 15, 16, 17, 255, 256, 257, 1000] {
+        let msg: Vec<u8> = (0..len).map(|_| rand::thread_rng().gen()).collect();
+        let aad = &msg[..len / 3];
+
+        let sealed = aead.encrypt(&nonce, Payload { msg: &msg, aad }).unwrap();
+        assert_eq!(sealed.len(), len + A::TagSize::USIZE);
+        assert_eq!(aead.decrypt(&nonce, Payload { msg: &sealed, aad }).unwrap(), msg);
+
+        for i in [0, sealed.len() / 2, sealed.len() - 1] {
+            let mut bad = sealed.clone();
+            bad[i] ^= 4;
+            assert!(aead.decrypt(&nonce, Payload { msg: &bad, aad }).is_err());
+        }
+        assert!(aead.decrypt(&nonce, Payload { msg: &sealed, aad: b"x" }).is_err());
+
+        let mut other = nonce.clone();
+        other[0] ^= 1;
+        assert!(aead.decrypt(&other, Payload { msg: &sealed, aad }).is_err());
+
+        let (c, tag) = sealed.split_at(len);
+        let mut buffer = c.to_vec();
+        let mut bad = Tag::<A>::clone_from_slice(tag);
+        bad[0] ^= 1;
+        assert!(aead.decrypt_in_place_detached(&nonce, aad, &mut buffer, &bad).is_err());
+        assert_eq!(buffer, c);
+    }
+}
@@ -0,0 +1,168 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use core::iter;
+
+use cipher::{consts::U16, BlockDecrypt, BlockEncrypt};
+
+use super::{blocks_mut, padding, xor, Block, Error, BATCH};
+
+
+/// Encrypts `data` in place, which must be a whole number of blocks. Every
+/// block depends on the previous one, so this runs one block at a time.
+pub fn encrypt<C>(cipher: &C, iv: &[u8; 16], data: &mut [u8]) -> Result<(), Error>
+where
+    C: BlockEncrypt<BlockSize = U16>,
+{
+    let mut prev = Block::from(*iv);
+    for block in blocks_mut(data)? {
+        xor(block, &prev);
+        cipher.encrypt_block(block);
+        prev = *block;
+    }
+    Ok(())
+}
+
+/// Decrypts `data` in place. The block decryptions are independent, they go
+/// through the cipher's batched path `BATCH` blocks at a time.
+pub fn decrypt<C>(cipher: &C, iv: &[u8; 16], data: &mut [u8]) -> Result<(), Error>
+where
+    C: BlockDecrypt<BlockSize = U16>,
+{
+    let mut prev = Block::from(*iv);
+    for chunk in blocks_mut(data)?.chunks_mut(BATCH) {
+        let mut ciphertext = [Block::default(); BATCH];
+        ciphertext[..chunk.len()].copy_from_slice(chunk);
+
+        cipher.decrypt_blocks(chunk);
+        for (block, c) in chunk.iter_mut().zip(iter::once(&prev).chain(&ciphertext)) {
+            xor(block, c);
+        }
+        prev = ciphertext[chunk.len() - 1];
+    }
+    Ok(())
+}
+
+/// Pads the first `len` bytes of `buf` and encrypts them in place, `buf` needs
+/// room for `padding::padded_len(len)` bytes.
+pub fn encrypt_padded<'a, C>(cipher: &C, iv: &[u8; 16], buf: &'a mut [u8], len: usize) -> Result<&'a [u8], Error>
+where
+    C: BlockEncrypt<BlockSize = U16>,
+{
+    let padded = padding::pad(buf, len)?;
+    encrypt(cipher, iv, padded)?;
+    Ok(padded)
+}
+
+/// Decrypts `buf` in place and returns the plaintext without padding.
+pub fn decrypt_padded<'a, C>(cipher: &C, iv: &[u8; 16], buf: &'a mut [u8]) -> Result<&'a [u8], Error>
+where
+    C: BlockDecrypt<BlockSize = U16>,
+{
+    decrypt(cipher, iv, buf)?;
+    padding::unpad(buf)
+}
+
+#[cfg(feature = "alloc")]
+pub fn encrypt_vec<C>(cipher: &C, iv: &[u8; 16], data: &[u8]) -> Vec<u8>
+where
+    C: BlockEncrypt<BlockSize = U16>,
+{
+    let mut buf = padding::pad_vec(data);
+    encrypt(cipher, iv, &mut buf).expect("padded to whole blocks");
+    buf
+}
+
+#[cfg(feature = "alloc")]
+pub fn decrypt_vec<C>(cipher: &C, iv: &[u8; 16], data: &[u8]) -> Result<Vec<u8>, Error>
+where
+    C: BlockDecrypt<BlockSize = U16>,
+{
+    let mut buf = data.to_vec();
+    let len = decrypt_padded(cipher, iv, &mut buf)?.len();
+    buf.truncate(len);
+    Ok(buf)
+}
+
+
+#[cfg(test)]
+use hex_literal::hex;
+
+// NIST SP 800-38A, F.2.1 and F.2.2
+#[test]
+fn test_aes_vectors(){
+    use aes::Aes128;
+    use cipher::KeyInit;
+
+    let aes = Aes128::new(&hex!("2b7e151628aed2a6abf7158809cf4f3c").into());
+    let iv = hex!("000102030405060708090a0b0c0d0e0f");
+    let plain = hex!(
+        "6bc1bee22e409f96e93d7e117393172a ae2d8a571e03ac9c9eb76fac45af8e51"
+        "30c81c46a35ce411e5fbc1191a0a52ef f69f2445df4f9b17ad2b417be66c3710"
+    );
+    let expected = hex!(
+        "7649abac8119b246cee98e9b12e9197d 5086cb9b507219ee95db113a917678b2"
+        "73bed6b8e3c1743b7116e69e22229516 3ff1caa1681fac09120eca307586e1a7"
+    );
+
+    let mut data = plain;
+    encrypt(&aes, &iv, &mut data).unwrap();
+    assert_eq!(data, expected);
+
+    decrypt(&aes, &iv, &mut data).unwrap();
+    assert_eq!(data, plain);
+}
+
+#[test]
+fn test_batched_decrypt(){
+    use crate::imp::{b256::Tekton256, Flags};
+    use rand::Rng;
+
+    let tekton = Tekton256::new(rand::thread_rng().gen(), Flags::default());
+    let iv: [u8; 16] = rand::thread_rng().gen();
+
+    // around multiples of BATCH, to check the chaining across batches
+    for n in [0, 1, 15, 16, 17, 32, 33, 50] {
+        let plain: Vec<u8> = (0..16 * n).map(|_| rand::thread_rng().gen()).collect();
+
+        let mut enc = plain.clone();
+        encrypt(&tekton, &iv, &mut enc).unwrap();
+
+        // one block at a time, straight from the definition
+        let mut prev = iv;
+        for (p, c) in plain.chunks(16).zip(enc.chunks(16)) {
+            let mut block: [u8; 16] = c.try_into().unwrap();
+            tekton.decrypt(&mut block);
+            xor(&mut block, &prev);
+            assert_eq!(block, p);
+            prev = c.try_into().unwrap();
+        }
+
+        decrypt(&tekton, &iv, &mut enc).unwrap();
+        assert_eq!(enc, plain);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_padded(){
+    use crate::imp::{b128::Tekton128, Flags};
+    use rand::Rng;
+
+    let tekton = Tekton128::new(rand::thread_rng().gen(), Flags::default());
+    let iv: [u8; 16] = rand::thread_rng().gen();
+
+    for len in [0, 1, 15, 16, 17, 255, 256, 1000] {
+        let data: Vec<u8> = (0..len).map(|_| rand::thread_rng().gen()).collect();
+        let enc = encrypt_vec(&tekton, &iv, &data);
+        assert_eq!(decrypt_vec(&tekton, &iv, &enc).unwrap(), data);
+    }
+
+    // the IV is XORed straight into the padding of a single block message
+    let enc = encrypt_vec(&tekton, &iv, b"attack at dawn");
+    let mut wrong_iv = iv;
+    wrong_iv[15] ^= 1;
+    assert_eq!(decrypt_vec(&tekton, &wrong_iv, &enc), Err(Error::InvalidPadding));
+
+    assert_eq!(decrypt_vec(&tekton, &iv, &enc[..15]), Err(Error::InvalidLength));
+}
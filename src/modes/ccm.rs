@@ -0,0 +1,347 @@
+use core::marker::PhantomData;
+
+use aead::{
+    consts::{U0, U13, U16}, generic_array::{ArrayLength, GenericArray},
+    AeadCore, AeadInPlace, Key, KeyInit, KeySizeUser, Nonce, Tag,
+};
+use cipher::{BlockEncrypt, StreamCipher};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+use super::ctr::{Ctr, Split};
+use super::{xor, Block, BLOCK_SIZE};
+
+
+/// CCM (NIST SP 800-38C, RFC 3610) with `M` byte tags, an even 4 to 16, and
+/// `N` byte nonces, 7 to 13. The message length field takes the remaining
+/// `L = 15 - N` bytes of the first block and bounds the payload to 2^(8L) - 1
+/// bytes, see `MAX_PAYLOAD`. IEEE 802.15.4 frames use `Ccm<C, M, U13>`.
+#[derive(Clone)]
+pub struct Ccm<C, M = U16, N = U13> {
+    cipher: C,
+    params: PhantomData<(M, N)>,
+}
+
+impl<C, M, N> From<C> for Ccm<C, M, N>
+where
+    C: BlockEncrypt<BlockSize = U16>,
+    M: ArrayLength<u8>,
+    N: ArrayLength<u8>,
+{
+    fn from(cipher: C) -> Self {
+        let () = Self::VALID_PARAMS;
+        Ccm { cipher, params: PhantomData }
+    }
+}
+
+impl<C: KeySizeUser, M, N> KeySizeUser for Ccm<C, M, N> {
+    type KeySize = C::KeySize;
+}
+
+impl<C, M, N> KeyInit for Ccm<C, M, N>
+where
+    C: BlockEncrypt<BlockSize = U16> + KeyInit,
+    M: ArrayLength<u8>,
+    N: ArrayLength<u8>,
+{
+    fn new(key: &Key<Self>) -> Self {
+        C::new(key).into()
+    }
+}
+
+impl<C, M: ArrayLength<u8>, N: ArrayLength<u8>> AeadCore for Ccm<C, M, N> {
+    type NonceSize = N;
+    type TagSize = M;
+    type CiphertextOverhead = U0;
+}
+
+impl<C, M, N> AeadInPlace for Ccm<C, M, N>
+where
+    C: BlockEncrypt<BlockSize = U16>,
+    M: ArrayLength<u8>,
+    N: ArrayLength<u8>,
+{
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> aead::Result<Tag<Self>> {
+        self.check_length(buffer)?;
+        let mac = self.cbc_mac(nonce, associated_data, buffer);
+        self.keystream(nonce).apply_keystream(buffer);
+        Ok(self.tag(nonce, mac))
+    }
+
+    /// On a tag mismatch the buffer is restored to the ciphertext.
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &Tag<Self>,
+    ) -> aead::Result<()> {
+        self.check_length(buffer)?;
+        self.keystream(nonce).apply_keystream(buffer);
+        let mac = self.cbc_mac(nonce, associated_data, buffer);
+        if self.tag(nonce, mac).ct_eq(tag).into() {
+            Ok(())
+        } else {
+            self.keystream(nonce).apply_keystream(buffer);
+            Err(aead::Error)
+        }
+    }
+}
+
+impl<C, M, N> Ccm<C, M, N>
+where
+    C: BlockEncrypt<BlockSize = U16>,
+    M: ArrayLength<u8>,
+    N: ArrayLength<u8>,
+{
+    const VALID_PARAMS: () = {
+        assert!(M::USIZE >= 4 && M::USIZE <= 16 && M::USIZE % 2 == 0, "CCM tags are 4, 6, .. 16 bytes");
+        assert!(N::USIZE >= 7 && N::USIZE <= 13, "CCM nonces are 7 to 13 bytes");
+    };
+
+    /// Size of the length field.
+    const L: usize = 15 - N::USIZE;
+
+    /// Longest payload the length field can encode.
+    pub const MAX_PAYLOAD: u64 = if Self::L >= 8 { u64::MAX } else { (1 << (8 * Self::L)) - 1 };
+
+    fn check_length(&self, payload: &[u8]) -> aead::Result<()> {
+        if payload.len() as u64 > Self::MAX_PAYLOAD {
+            return Err(aead::Error);
+        }
+        Ok(())
+    }
+
+    /// Flags, nonce and `i` in the length field, A_i of the specification.
+    fn counter_block(&self, nonce: &Nonce<Self>, i: u64) -> [u8; 16] {
+        let mut block = [0; 16];
+        block[0] = (Self::L - 1) as u8;
+        block[1..=N::USIZE].copy_from_slice(nonce);
+        (u128::from_be_bytes(block) | i as u128).to_be_bytes()
+    }
+
+    // i < 2^(8L) for every block of a valid payload, so the counter never
+    // carries into the nonce bytes that share its 64 bits
+    fn keystream(&self, nonce: &Nonce<Self>) -> Ctr<&C> {
+        Ctr::new(&self.cipher, Split::Nonce64Counter64, &self.counter_block(nonce, 1))
+    }
+
+    fn cbc_mac(&self, nonce: &Nonce<Self>, associated_data: &[u8], payload: &[u8]) -> Block {
+        let mut b0 = [0; 16];
+        b0[0] = (!associated_data.is_empty() as u8) << 6
+            | (((M::USIZE - 2) / 2) as u8) << 3
+            | (Self::L - 1) as u8;
+        b0[1..=N::USIZE].copy_from_slice(nonce);
+        b0[N::USIZE + 1..].copy_from_slice(&(payload.len() as u128).to_be_bytes()[N::USIZE + 1..]);
+
+        let mut mac = CbcMac::new(&self.cipher, b0);
+        if !associated_data.is_empty() {
+            let len = associated_data.len() as u64;
+            if len < 0xff00 {
+                mac.update(&(len as u16).to_be_bytes());
+            } else if len <= u32::MAX as u64 {
+                mac.update(&[0xff, 0xfe]);
+                mac.update(&(len as u32).to_be_bytes());
+            } else {
+                mac.update(&[0xff, 0xff]);
+                mac.update(&len.to_be_bytes());
+            }
+            mac.update(associated_data);
+            mac.pad();
+        }
+        mac.update(payload);
+        mac.pad();
+        mac.state
+    }
+
+    fn tag(&self, nonce: &Nonce<Self>, mac: Block) -> Tag<Self> {
+        let mut s0 = Block::from(self.counter_block(nonce, 0));
+        self.cipher.encrypt_block(&mut s0);
+        xor(&mut s0, &mac);
+        GenericArray::clone_from_slice(&s0[..M::USIZE])
+    }
+}
+
+
+/// CBC-MAC over segments that are each zero padded to a whole block.
+struct CbcMac<'a, C> {
+    cipher: &'a C,
+    state: Block,
+    pos: usize,
+}
+
+impl<'a, C: BlockEncrypt<BlockSize = U16>> CbcMac<'a, C> {
+
+    fn new(cipher: &'a C, b0: [u8; 16]) -> Self {
+        let mut state = Block::from(b0);
+        cipher.encrypt_block(&mut state);
+        CbcMac { cipher, state, pos: 0 }
+    }
+
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let n = data.len().min(BLOCK_SIZE - self.pos);
+            xor(&mut self.state[self.pos..self.pos + n], &data[..n]);
+            self.pos += n;
+            data = &data[n..];
+            if self.pos == BLOCK_SIZE {
+                self.cipher.encrypt_block(&mut self.state);
+                self.pos = 0;
+            }
+        }
+    }
+
+    fn pad(&mut self) {
+        if self.pos != 0 {
+            self.cipher.encrypt_block(&mut self.state);
+            self.pos = 0;
+        }
+    }
+}
+
+impl<C> Drop for CbcMac<'_, C> {
+    fn drop(&mut self) {
+        self.state.as_mut_slice().zeroize();
+    }
+}
+
+
+#[cfg(test)]
+use hex_literal::hex;
+
+#[cfg(test)]
+fn check_vector<M, N>(key: &[u8], nonce: &[u8], ad: &[u8], plain: &[u8], expected: &[u8])
+where
+    M: ArrayLength<u8>,
+    N: ArrayLength<u8>,
+{
+    use aes::Aes128;
+
+    let ccm = Ccm::<Aes128, M, N>::new_from_slice(key).unwrap();
+    let nonce = Nonce::<Ccm<Aes128, M, N>>::from_slice(nonce);
+    let (ciphertext, tag) = expected.split_at(plain.len());
+
+    let mut buffer = [0; 32];
+    let buffer = &mut buffer[..plain.len()];
+    buffer.copy_from_slice(plain);
+    let t = ccm.encrypt_in_place_detached(nonce, ad, buffer).unwrap();
+    assert_eq!(buffer, ciphertext);
+    assert_eq!(t.as_slice(), tag);
+
+    ccm.decrypt_in_place_detached(nonce, ad, buffer, &t).unwrap();
+    assert_eq!(buffer, plain);
+}
+
+// NIST SP 800-38C, appendix C, examples 1 to 4
+#[test]
+fn test_nist_vectors(){
+    use aead::consts::{U4, U6, U7, U8, U12, U14};
+
+    let key = hex!("404142434445464748494a4b4c4d4e4f");
+    let plain: [u8; 32] = core::array::from_fn(|i| 0x20 + i as u8);
+
+    check_vector::<U4, U7>(
+        &key, &hex!("10111213141516"), &hex!("0001020304050607"), &plain[..4],
+        &hex!("7162015b4dac255d"),
+    );
+    check_vector::<U6, U8>(
+        &key, &hex!("1011121314151617"), &hex!("000102030405060708090a0b0c0d0e0f"), &plain[..16],
+        &hex!("d2a1f0e051ea5f62081a7792073d593d1fc64fbfaccd"),
+    );
+    check_vector::<U8, U12>(
+        &key, &hex!("101112131415161718191a1b"), &hex!("000102030405060708090a0b0c0d0e0f10111213"),
+        &plain[..24],
+        &hex!("e3b201a9f5b71a7a9b1ceaeccd97e70b6176aad9a4428aa5484392fbc1b09951"),
+    );
+
+    // 2^16 bytes of associated data take the six byte length encoding
+    let ad: [u8; 65536] = core::array::from_fn(|i| i as u8);
+    check_vector::<U14, U13>(
+        &key, &hex!("101112131415161718191a1b1c"), &ad, &plain,
+        &hex!(
+            "69915dad1e84c6376a68c2967e4dab615ae0fd1faec44cc484828529463ccf72"
+            "b4ac6bec93e8598e7f0dadbcea5b"
+        ),
+    );
+}
+
+// RFC 3610, packet vectors #1 to #3, #9 and #10
+#[test]
+fn test_rfc3610_vectors(){
+    use aead::consts::{U8, U10};
+
+    let key = hex!("c0c1c2c3c4c5c6c7c8c9cacbcccdcecf");
+    let ad = hex!("0001020304050607");
+    let plain: [u8; 25] = core::array::from_fn(|i| 0x08 + i as u8);
+
+    check_vector::<U8, U13>(
+        &key, &hex!("00000003020100a0a1a2a3a4a5"), &ad, &plain[..23],
+        &hex!("588c979a61c663d2f066d0c2c0f989806d5f6b61dac38417e8d12cfdf926e0"),
+    );
+    check_vector::<U8, U13>(
+        &key, &hex!("00000004030201a0a1a2a3a4a5"), &ad, &plain[..24],
+        &hex!("72c91a36e135f8cf291ca894085c87e3cc15c439c9e43a3ba091d56e10400916"),
+    );
+    check_vector::<U8, U13>(
+        &key, &hex!("00000005040302a0a1a2a3a4a5"), &ad, &plain[..25],
+        &hex!("51b1e5f44a197d1da46b0f8e2d282ae871e838bb64da8596574adaa76fbd9fb0c5"),
+    );
+    check_vector::<U10, U13>(
+        &key, &hex!("00000009080706a0a1a2a3a4a5"), &ad, &plain[..23],
+        &hex!("0135d1b2c95f41d5d1d4fec185d166b8094e999dfed96c048c56602c97acbb7490"),
+    );
+    check_vector::<U10, U13>(
+        &key, &hex!("0000000a090807a0a1a2a3a4a5"), &ad, &plain[..24],
+        &hex!("7b75399ac0831dd2f0bbd75879a2fd8f6cae6b6cd9b7db24c17b4433f434963f34b4"),
+    );
+}
+
+#[test]
+fn test_max_payload(){
+    use aes::Aes128;
+    use aead::consts::{U7, U8, U10};
+
+    assert_eq!(Ccm::<Aes128, U8, U13>::MAX_PAYLOAD, 0xffff);
+    assert_eq!(Ccm::<Aes128, U8, U7>::MAX_PAYLOAD, u64::MAX);
+
+    let ccm = Ccm::<Aes128, U10, U13>::new(&Default::default());
+    let nonce = Default::default();
+    let mut buffer = [0; 0x10000];
+    assert!(ccm.encrypt_in_place_detached(&nonce, &[], &mut buffer).is_err());
+
+    let tag = ccm.encrypt_in_place_detached(&nonce, &[], &mut buffer[..0xffff]).unwrap();
+    ccm.decrypt_in_place_detached(&nonce, &[], &mut buffer[..0xffff], &tag).unwrap();
+    assert_eq!(buffer, [0; 0x10000]);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_tekton(){
+    use crate::imp::{b128::Tekton128, b256::Tekton256};
+    use aead::{Aead, consts::{U4, U7}};
+    use rand::Rng;
+
+    let key: [u8; 16] = rand::thread_rng().gen();
+    super::check_aead(&Ccm::<Tekton128>::new(&key.into()));
+    super::check_aead(&Ccm::<Tekton128, U4, U7>::new(&key.into()));
+    super::check_aead(&Ccm::<Tekton256>::new(&rand::thread_rng().gen::<[u8; 32]>().into()));
+
+    // M goes into the MAC only, so the ciphertext stays and a short tag is no
+    // prefix of a long one
+    let nonce: [u8; 7] = rand::thread_rng().gen();
+    let long = Ccm::<Tekton128, U16, U7>::new(&key.into()).encrypt(&nonce.into(), &[0; 40][..]).unwrap();
+    let short = Ccm::<Tekton128, U4, U7>::new(&key.into()).encrypt(&nonce.into(), &[0; 40][..]).unwrap();
+    assert_eq!(long[..40], short[..40]);
+    assert_ne!(long[40..44], short[40..]);
+
+    // zero padding the nonce gives the same counter blocks but for L in their flags
+    let mut nonce13 = [0; 13];
+    nonce13[..7].copy_from_slice(&nonce);
+    let wide = Ccm::<Tekton128, U16, U13>::new(&key.into()).encrypt(&nonce13.into(), &[0; 40][..]).unwrap();
+    assert_ne!(long[..40], wide[..40]);
+}
@@ -0,0 +1,204 @@
+use cipher::{consts::U16, BlockEncrypt};
+
+use super::{Block, BATCH, BLOCK_SIZE};
+
+
+/// CFB with full block feedback. Data can be fed in pieces of any length, use
+/// one object per direction.
+#[derive(Clone)]
+pub struct Cfb128<C> {
+    cipher: C,
+    // ciphertext bytes [..pos] of the current block, keystream bytes [pos..]
+    state: Block,
+    pos: usize,
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> Cfb128<C> {
+
+    pub fn new(cipher: C, iv: &[u8; 16]) -> Self {
+        let mut state = Block::from(*iv);
+        cipher.encrypt_block(&mut state);
+        Cfb128 { cipher, state, pos: 0 }
+    }
+
+    pub fn encrypt(&mut self, data: &mut [u8]) {
+        for b in data {
+            self.state[self.pos] ^= *b;
+            *b = self.state[self.pos];
+            self.advance();
+        }
+    }
+
+    /// Whole blocks at a block boundary go through the cipher's batched path,
+    /// their keystream only depends on ciphertext that is already known.
+    pub fn decrypt(&mut self, mut data: &mut [u8]) {
+        while self.pos != 0 && !data.is_empty() {
+            let (b, rest) = data.split_first_mut().unwrap();
+            self.decrypt_byte(b);
+            data = rest;
+        }
+
+        let mut chunks = data.chunks_exact_mut(BATCH * BLOCK_SIZE);
+        for chunk in &mut chunks {
+            self.decrypt_batch(chunk);
+        }
+        let rest = chunks.into_remainder();
+        let whole = rest.len() - rest.len() % BLOCK_SIZE;
+        self.decrypt_batch(&mut rest[..whole]);
+
+        for b in &mut rest[whole..] {
+            self.decrypt_byte(b);
+        }
+    }
+
+    #[inline]
+    fn decrypt_byte(&mut self, b: &mut u8) {
+        let c = *b;
+        *b ^= self.state[self.pos];
+        self.state[self.pos] = c;
+        self.advance();
+    }
+
+    #[inline]
+    fn advance(&mut self) {
+        self.pos += 1;
+        if self.pos == BLOCK_SIZE {
+            self.cipher.encrypt_block(&mut self.state);
+            self.pos = 0;
+        }
+    }
+
+    // up to BATCH whole blocks, starting at a block boundary
+    fn decrypt_batch(&mut self, data: &mut [u8]) {
+        let n = data.len() / BLOCK_SIZE;
+        if n == 0 {
+            return;
+        }
+
+        let mut keystream = [Block::default(); BATCH];
+        for (k, c) in keystream.iter_mut().zip(data.chunks_exact(BLOCK_SIZE)) {
+            k.copy_from_slice(c);
+        }
+        self.cipher.encrypt_blocks(&mut keystream[..n]);
+
+        for (i, block) in data.chunks_exact_mut(BLOCK_SIZE).enumerate() {
+            let k = if i == 0 { &self.state } else { &keystream[i - 1] };
+            super::xor(block, k);
+        }
+        self.state = keystream[n - 1];
+    }
+}
+
+
+/// CFB with 8-bit feedback, one block encryption per byte.
+#[derive(Clone)]
+pub struct Cfb8<C> {
+    cipher: C,
+    register: [u8; 16],
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> Cfb8<C> {
+
+    pub fn new(cipher: C, iv: &[u8; 16]) -> Self {
+        Cfb8 { cipher, register: *iv }
+    }
+
+    pub fn encrypt(&mut self, data: &mut [u8]) {
+        for b in data {
+            *b ^= self.keystream_byte();
+            self.shift_in(*b);
+        }
+    }
+
+    pub fn decrypt(&mut self, data: &mut [u8]) {
+        for b in data {
+            let c = *b;
+            *b ^= self.keystream_byte();
+            self.shift_in(c);
+        }
+    }
+
+    #[inline]
+    fn keystream_byte(&self) -> u8 {
+        let mut block = Block::from(self.register);
+        self.cipher.encrypt_block(&mut block);
+        block[0]
+    }
+
+    #[inline]
+    fn shift_in(&mut self, c: u8) {
+        self.register.copy_within(1.., 0);
+        self.register[BLOCK_SIZE - 1] = c;
+    }
+}
+
+
+#[cfg(test)]
+use hex_literal::hex;
+
+// NIST SP 800-38A, F.3.7, F.3.8, F.3.13 and F.3.14
+#[test]
+fn test_aes_vectors(){
+    use aes::Aes128;
+    use cipher::KeyInit;
+
+    let aes = Aes128::new(&hex!("2b7e151628aed2a6abf7158809cf4f3c").into());
+    let iv = hex!("000102030405060708090a0b0c0d0e0f");
+
+    let plain = hex!("6bc1bee22e409f96e93d7e117393172aae2d");
+    let mut data = plain;
+    Cfb8::new(aes.clone(), &iv).encrypt(&mut data);
+    assert_eq!(data, hex!("3b79424c9c0dd436bace9e0ed4586a4f32b9"));
+    Cfb8::new(aes.clone(), &iv).decrypt(&mut data);
+    assert_eq!(data, plain);
+
+    let plain = hex!(
+        "6bc1bee22e409f96e93d7e117393172a ae2d8a571e03ac9c9eb76fac45af8e51"
+        "30c81c46a35ce411e5fbc1191a0a52ef f69f2445df4f9b17ad2b417be66c3710"
+    );
+    let mut data = plain;
+    Cfb128::new(aes.clone(), &iv).encrypt(&mut data);
+    assert_eq!(data, hex!(
+        "3b3fd92eb72dad20333449f8e83cfb4a c8a64537a0b3a93fcde3cdad9f1ce58b"
+        "26751f67a3cbb140b1808cf187a4f4df c04b05357c5d1c0eeac4c66f9ff7f2e6"
+    ));
+    Cfb128::new(aes, &iv).decrypt(&mut data);
+    assert_eq!(data, plain);
+}
+
+#[test]
+fn test_incremental(){
+    use crate::imp::{b128::Tekton128, Flags};
+    use rand::Rng;
+
+    let tekton = Tekton128::new(rand::thread_rng().gen(), Flags::default());
+    let iv: [u8; 16] = rand::thread_rng().gen();
+    let plain: Vec<u8> = (0..1000).map(|_| rand::thread_rng().gen()).collect();
+
+    let mut expected = plain.clone();
+    Cfb128::new(tekton.clone(), &iv).encrypt(&mut expected);
+
+    // random piece lengths, to hit the byte path, short batches and full ones
+    for _ in 0..20 {
+        let mut enc = plain.clone();
+        let mut cfb = Cfb128::new(tekton.clone(), &iv);
+        let mut rest = &mut enc[..];
+        while !rest.is_empty() {
+            let n = rand::thread_rng().gen_range(0..=rest.len().min(300));
+            let (piece, tail) = rest.split_at_mut(n);
+            cfb.encrypt(piece);
+            rest = tail;
+        }
+        assert_eq!(enc, expected);
+
+        let mut cfb = Cfb128::new(tekton.clone(), &iv);
+        let mut rest = &mut enc[..];
+        while !rest.is_empty() {
+            let n = rand::thread_rng().gen_range(0..=rest.len().min(300));
+            let (piece, tail) = rest.split_at_mut(n);
+            cfb.decrypt(piece);
+            rest = tail;
+        }
+        assert_eq!(enc, plain);
+    }
+}
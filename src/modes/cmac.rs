@@ -0,0 +1,212 @@
+use cipher::{consts::U16, BlockEncrypt, Key, KeySizeUser};
+use digest::{FixedOutput, FixedOutputReset, MacMarker, Output, OutputSizeUser};
+use zeroize::Zeroize;
+
+use super::{dbl, xor, Block, BLOCK_SIZE};
+
+
+/// CMAC (NIST SP 800-38B), also known as OMAC1, behind the `digest::Mac`
+/// traits, also built from a keyed cipher through `From<C>`. Tags can be cut
+/// to their leftmost bytes and checked with `Mac::verify_truncated_left`.
+#[derive(Clone)]
+pub struct Cmac<C> {
+    cipher: C,
+    k1: [u8; 16],
+    k2: [u8; 16],
+    state: Block,
+    // the last block is held back until `finalize`, it gets a subkey
+    buffer: [u8; 16],
+    pos: usize,
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> From<C> for Cmac<C> {
+    fn from(cipher: C) -> Self {
+        let mut l = Block::default();
+        cipher.encrypt_block(&mut l);
+        let k1 = dbl(u128::from_be_bytes(l.into()));
+        let k2 = dbl(k1);
+        l.as_mut_slice().zeroize();
+        Cmac {
+            cipher,
+            k1: k1.to_be_bytes(),
+            k2: k2.to_be_bytes(),
+            state: Block::default(),
+            buffer: [0; 16],
+            pos: 0,
+        }
+    }
+}
+
+impl<C: KeySizeUser> KeySizeUser for Cmac<C> {
+    type KeySize = C::KeySize;
+}
+
+// `KeyInit`, `Update` and `Reset` are not imported, their methods would be
+// ambiguous with the `Mac` ones
+impl<C: BlockEncrypt<BlockSize = U16> + cipher::KeyInit> cipher::KeyInit for Cmac<C> {
+    fn new(key: &Key<Self>) -> Self {
+        C::new(key).into()
+    }
+}
+
+impl<C> OutputSizeUser for Cmac<C> {
+    type OutputSize = U16;
+}
+
+impl<C> MacMarker for Cmac<C> {}
+
+impl<C: BlockEncrypt<BlockSize = U16>> digest::Update for Cmac<C> {
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.pos == BLOCK_SIZE {
+                xor(&mut self.state, &self.buffer);
+                self.cipher.encrypt_block(&mut self.state);
+                self.pos = 0;
+            }
+            let n = data.len().min(BLOCK_SIZE - self.pos);
+            self.buffer[self.pos..self.pos + n].copy_from_slice(&data[..n]);
+            self.pos += n;
+            data = &data[n..];
+        }
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> FixedOutput for Cmac<C> {
+    fn finalize_into(mut self, out: &mut Output<Self>) {
+        self.finalize_into_reset(out);
+    }
+}
+
+impl<C> digest::Reset for Cmac<C> {
+    fn reset(&mut self) {
+        self.state.as_mut_slice().zeroize();
+        self.buffer.zeroize();
+        self.pos = 0;
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> FixedOutputReset for Cmac<C> {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        let mut last = self.buffer;
+        if self.pos == BLOCK_SIZE {
+            xor(&mut last, &self.k1);
+        } else {
+            last[self.pos] = 0x80;
+            last[self.pos + 1..].fill(0);
+            xor(&mut last, &self.k2);
+        }
+        xor(&mut self.state, &last);
+        self.cipher.encrypt_block(&mut self.state);
+        last.zeroize();
+        *out = self.state;
+        digest::Reset::reset(self);
+    }
+}
+
+impl<C> Cmac<C> {
+
+    pub(crate) fn cipher(&self) -> &C {
+        &self.cipher
+    }
+}
+
+impl<C> Drop for Cmac<C> {
+    fn drop(&mut self) {
+        self.k1.zeroize();
+        self.k2.zeroize();
+        self.state.as_mut_slice().zeroize();
+        self.buffer.zeroize();
+    }
+}
+
+
+#[cfg(test)]
+use digest::Mac;
+
+#[cfg(test)]
+use hex_literal::hex;
+
+// RFC 4493, section 4, and the AES-256 examples of NIST SP 800-38B
+#[test]
+fn test_aes_vectors(){
+    use aes::{Aes128, Aes256};
+
+    let msg = hex!(
+        "6bc1bee22e409f96e93d7e117393172a ae2d8a571e03ac9c9eb76fac45af8e51"
+        "30c81c46a35ce411e5fbc1191a0a52ef f69f2445df4f9b17ad2b417be66c3710"
+    );
+
+    let mac = Cmac::<Aes128>::new(&hex!("2b7e151628aed2a6abf7158809cf4f3c").into());
+    assert_eq!(mac.k1, hex!("fbeed618357133667c85e08f7236a8de"));
+    assert_eq!(mac.k2, hex!("f7ddac306ae266ccf90bc11ee46d513b"));
+
+    let vectors = [
+        (0, hex!("bb1d6929e95937287fa37d129b756746")),
+        (16, hex!("070a16b46b4d4144f79bdd9dd04a287c")),
+        (40, hex!("dfa66747de9ae63030ca32611497c827")),
+        (64, hex!("51f0bebf7e3b9d92fc49741779363cfe")),
+    ];
+    for (len, tag) in vectors {
+        let mut m = mac.clone();
+        m.update(&msg[..len]);
+        assert_eq!(m.finalize().into_bytes(), tag.into());
+
+        // byte at a time, then reusing the state after a reset
+        let mut m = mac.clone();
+        for b in &msg[..len] {
+            m.update(core::slice::from_ref(b));
+        }
+        assert_eq!(m.finalize_reset().into_bytes(), tag.into());
+        m.update(&msg[..len]);
+        m.verify_slice(&tag).unwrap();
+    }
+
+    let mac = Cmac::<Aes256>::new(&hex!(
+        "603deb1015ca71be2b73aef0857d77811f352c073b6108d72d9810a30914dff4"
+    ).into());
+    let vectors = [
+        (0, hex!("028962f61b7bf89efc6b551f4667d983")),
+        (16, hex!("28a7023f452e8f82bd4bf28d8c37c35c")),
+        (40, hex!("aaf3d8f1de5640c232f5b169b9c911e6")),
+        (64, hex!("e1992190549f6ed5696a2c056c315410")),
+    ];
+    for (len, tag) in vectors {
+        mac.clone().chain_update(&msg[..len]).verify(&tag.into()).unwrap();
+    }
+}
+
+#[test]
+fn test_tekton(){
+    use crate::imp::{b128::Tekton128, b256::Tekton256};
+    use rand::Rng;
+
+    fn check<C: BlockEncrypt<BlockSize = U16> + cipher::KeyInit + Clone>(key: &Key<C>) {
+        let mut msg = [0u8; 100];
+        rand::thread_rng().fill(&mut msg[..]);
+        let mac = Cmac::<C>::new(key);
+        let tag = mac.clone().chain_update(msg).finalize().into_bytes();
+
+        for split in [0, 1, 15, 16, 17, 99, 100] {
+            let mut m = mac.clone();
+            m.update(&msg[..split]);
+            m.update(&msg[split..]);
+            m.verify_slice(&tag).unwrap();
+        }
+
+        // a truncated tag checks against its leftmost bytes only
+        mac.clone().chain_update(msg).verify_truncated_left(&tag[..8]).unwrap();
+        assert!(mac.clone().chain_update(msg).verify_truncated_left(&tag[8..]).is_err());
+
+        let mut bad = msg;
+        bad[50] ^= 1;
+        assert!(mac.clone().chain_update(bad).verify_slice(&tag).is_err());
+        assert!(mac.clone().chain_update(&msg[..99]).verify_slice(&tag).is_err());
+
+        let mut other = key.clone();
+        other[0] ^= 1;
+        assert!(Cmac::<C>::new(&other).chain_update(msg).verify_slice(&tag).is_err());
+    }
+
+    check::<Tekton128>(&rand::thread_rng().gen::<[u8; 16]>().into());
+    check::<Tekton256>(&rand::thread_rng().gen::<[u8; 32]>().into());
+}
@@ -0,0 +1,264 @@
+use cipher::{
+    consts::U16, BlockEncrypt, BlockSizeUser, OverflowError, ParBlocks, ParBlocksSizeUser,
+    SeekNum, StreamBackend, StreamCipher, StreamCipherCore, StreamCipherCoreWrapper,
+    StreamCipherError, StreamCipherSeek, StreamCipherSeekCore, StreamClosure,
+    inout::InOutBuf,
+};
+
+use super::Block;
+
+
+/// How the 16 byte counter block is divided between nonce and big-endian counter.
+/// The counter wraps within its own bits and never carries into the nonce.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Split {
+    Nonce96Counter32,
+    Nonce64Counter64,
+    /// The whole block is the counter, as in NIST SP 800-38A.
+    Counter128,
+}
+
+impl Split {
+    fn counter_mask(&self) -> u128 {
+        match self {
+            Split::Nonce96Counter32 => u32::MAX as u128,
+            Split::Nonce64Counter64 => u64::MAX as u128,
+            Split::Counter128 => u128::MAX,
+        }
+    }
+}
+
+
+/// Block level CTR, `Ctr` adds the byte buffering on top.
+#[derive(Clone)]
+pub struct CtrCore<C> {
+    cipher: C,
+    iv: u128,
+    mask: u128,
+    // blocks after the initial counter, and the largest valid one
+    pos: u64,
+    last: u128,
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> CtrCore<C> {
+
+    /// `iv` is the nonce followed by the initial counter value.
+    pub fn new(cipher: C, split: Split, iv: &[u8; 16]) -> Self {
+        let iv = u128::from_be_bytes(*iv);
+        let mask = split.counter_mask();
+        CtrCore { cipher, iv, mask, pos: 0, last: mask - (iv & mask) }
+    }
+
+    #[inline]
+    fn counter_block(&self, pos: u64) -> Block {
+        let counter = (self.iv & self.mask).wrapping_add(pos as u128) & self.mask;
+        (self.iv & !self.mask | counter).to_be_bytes().into()
+    }
+}
+
+impl<C> BlockSizeUser for CtrCore<C> {
+    type BlockSize = U16;
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> StreamCipherCore for CtrCore<C> {
+    fn remaining_blocks(&self) -> Option<usize> {
+        let pos = self.pos as u128;
+        if pos > self.last {
+            return Some(0);
+        }
+        usize::try_from(self.last - pos).ok()?.checked_add(1)
+    }
+
+    fn process_with_backend(&mut self, f: impl StreamClosure<BlockSize = Self::BlockSize>) {
+        f.call(&mut CtrBackend(self));
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> StreamCipherSeekCore for CtrCore<C> {
+    type Counter = u64;
+
+    fn get_block_pos(&self) -> u64 {
+        self.pos
+    }
+
+    fn set_block_pos(&mut self, pos: u64) {
+        self.pos = pos;
+    }
+}
+
+
+struct CtrBackend<'a, C>(&'a mut CtrCore<C>);
+
+impl<C> BlockSizeUser for CtrBackend<'_, C> {
+    type BlockSize = U16;
+}
+
+impl<C> ParBlocksSizeUser for CtrBackend<'_, C> {
+    type ParBlocksSize = U16;
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> StreamBackend for CtrBackend<'_, C> {
+    #[inline]
+    fn gen_ks_block(&mut self, block: &mut Block) {
+        *block = self.0.counter_block(self.0.pos);
+        self.0.cipher.encrypt_block(block);
+        self.0.pos = self.0.pos.wrapping_add(1);
+    }
+
+    #[inline]
+    fn gen_par_ks_blocks(&mut self, blocks: &mut ParBlocks<Self>) {
+        for (i, block) in blocks.iter_mut().enumerate() {
+            *block = self.0.counter_block(self.0.pos.wrapping_add(i as u64));
+        }
+        self.0.cipher.encrypt_blocks(blocks);
+        self.0.pos = self.0.pos.wrapping_add(blocks.len() as u64);
+    }
+}
+
+
+/// CTR mode over any 128-bit block cipher, keystream blocks are generated
+/// through the cipher's batched path. Applying keystream past the end of the
+/// counter range fails with `StreamCipherError` (`apply_keystream` panics),
+/// as does seeking there.
+#[derive(Clone)]
+pub struct Ctr<C: BlockEncrypt<BlockSize = U16>> {
+    inner: StreamCipherCoreWrapper<CtrCore<C>>,
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> Ctr<C> {
+
+    /// `iv` is the nonce followed by the initial counter value, which is
+    /// usually zero. A nonce must never be reused with the same key.
+    pub fn new(cipher: C, split: Split, iv: &[u8; 16]) -> Self {
+        Ctr { inner: StreamCipherCoreWrapper::from_core(CtrCore::new(cipher, split, iv)) }
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> StreamCipher for Ctr<C> {
+    #[inline]
+    fn try_apply_keystream_inout(&mut self, buf: InOutBuf<'_, '_, u8>) -> Result<(), StreamCipherError> {
+        self.inner.try_apply_keystream_inout(buf)
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> StreamCipherSeek for Ctr<C> {
+    fn try_current_pos<SN: SeekNum>(&self) -> Result<SN, OverflowError> {
+        self.inner.try_current_pos()
+    }
+
+    // the wrapper would happily move past the last counter value
+    fn try_seek<SN: SeekNum>(&mut self, pos: SN) -> Result<(), StreamCipherError> {
+        let (block, byte) = pos.into_block_byte::<u64>(16)?;
+        let last = self.inner.get_core().last;
+        // a block-aligned position may sit just past the last block, `last` can be u128::MAX
+        if (block as u128).saturating_sub((byte == 0) as u128) > last {
+            return Err(StreamCipherError);
+        }
+        self.inner.try_seek(block as u128 * 16 + byte as u128)
+    }
+}
+
+
+#[cfg(test)]
+use hex_literal::hex;
+
+// NIST SP 800-38A, F.5.1 and F.5.2
+#[test]
+fn test_aes_vectors(){
+    use aes::Aes128;
+    use cipher::KeyInit;
+
+    let aes = Aes128::new(&hex!("2b7e151628aed2a6abf7158809cf4f3c").into());
+    let iv = hex!("f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff");
+    let plain = hex!(
+        "6bc1bee22e409f96e93d7e117393172a ae2d8a571e03ac9c9eb76fac45af8e51"
+        "30c81c46a35ce411e5fbc1191a0a52ef f69f2445df4f9b17ad2b417be66c3710"
+    );
+    let expected = hex!(
+        "874d6191b620e3261bef6864990db6ce 9806f66b7970fdff8617187bb9fffdff"
+        "5ae4df3edbd5d35e5b4f09020db03eab 1e031dda2fbe03d1792170a0f3009cee"
+    );
+
+    // the counter never leaves the low 32 bits here, all splits agree
+    for split in [Split::Nonce96Counter32, Split::Nonce64Counter64, Split::Counter128] {
+        let mut data = plain;
+        Ctr::new(aes.clone(), split, &iv).apply_keystream(&mut data);
+        assert_eq!(data, expected);
+
+        // odd chunk sizes go through the keystream buffer
+        let mut ctr = Ctr::new(aes.clone(), split, &iv);
+        for chunk in data.chunks_mut(7) {
+            ctr.apply_keystream(chunk);
+        }
+        assert_eq!(data, plain);
+    }
+}
+
+#[test]
+fn test_seek(){
+    use crate::imp::{b128::Tekton128, Flags};
+    use rand::Rng;
+
+    let tekton = Tekton128::new(rand::thread_rng().gen(), Flags::default());
+    let iv: [u8; 16] = rand::thread_rng().gen();
+
+    let mut keystream = [0; 1000];
+    let mut ctr = Ctr::new(tekton.clone(), Split::Nonce64Counter64, &iv);
+    ctr.apply_keystream(&mut keystream);
+    assert_eq!(ctr.current_pos::<u64>(), 1000);
+
+    for _ in 0..100 {
+        let start = rand::thread_rng().gen_range(0..1000);
+        let end = rand::thread_rng().gen_range(start..=1000);
+
+        ctr.seek(start as u64);
+        let mut part = [0; 1000];
+        ctr.apply_keystream(&mut part[start..end]);
+        assert_eq!(part[start..end], keystream[start..end]);
+        assert_eq!(ctr.current_pos::<usize>(), end);
+    }
+
+    // a zero 128-bit counter leaves the whole range ahead
+    let mut keystream = [0; 64];
+    let mut ctr = Ctr::new(tekton, Split::Counter128, &[0; 16]);
+    ctr.apply_keystream(&mut keystream);
+    for pos in [0u64, 16, 17, 32, 48, 64] {
+        ctr.try_seek(pos).unwrap();
+        let mut part = [0; 64];
+        ctr.apply_keystream(&mut part[pos as usize..]);
+        assert_eq!(part[pos as usize..], keystream[pos as usize..]);
+    }
+    assert!(ctr.try_seek(u64::MAX).is_ok());
+}
+
+#[test]
+fn test_counter_overflow(){
+    use crate::imp::{b128::Tekton128, Flags, TektonCipher};
+    use rand::Rng;
+
+    let tekton = Tekton128::new(rand::thread_rng().gen(), Flags::default());
+    let mut iv: [u8; 16] = rand::thread_rng().gen();
+    iv[12..].copy_from_slice(&[0xff, 0xff, 0xff, 0xfe]);
+
+    let mut ctr = Ctr::new(tekton.clone(), Split::Nonce96Counter32, &iv);
+    let mut data = [0; 33];
+    assert!(ctr.try_apply_keystream(&mut data).is_err());
+    assert!(ctr.try_apply_keystream(&mut data[..20]).is_ok());
+    assert!(ctr.try_apply_keystream(&mut data[20..32]).is_ok());
+    assert!(ctr.try_apply_keystream(&mut data[..1]).is_err());
+
+    // the last keystream block uses counter 0xffffffff, the nonce is untouched
+    let mut last = iv;
+    last[15] = 0xff;
+    TektonCipher::encrypt_block(&tekton, &mut last);
+    assert_eq!(data[16..32], last);
+
+    assert!(ctr.try_seek(32u64).is_ok());
+    assert!(ctr.try_seek(33u64).is_err());
+    assert!(ctr.try_seek(31u64).is_ok());
+    assert!(ctr.try_apply_keystream(&mut data[..1]).is_ok());
+
+    let mut ctr = Ctr::new(tekton, Split::Nonce64Counter64, &iv);
+    assert!(ctr.try_seek(1u64 << 40).is_ok());
+    assert!(ctr.try_apply_keystream(&mut data).is_ok());
+}
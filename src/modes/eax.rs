@@ -0,0 +1,252 @@
+use core::marker::PhantomData;
+
+use aead::{
+    consts::{U0, U16}, generic_array::{ArrayLength, GenericArray},
+    AeadCore, AeadInPlace, Key, KeyInit, KeySizeUser, Nonce, Tag,
+};
+use cipher::{BlockEncrypt, StreamCipher};
+use digest::Mac;
+use subtle::ConstantTimeEq;
+
+use super::cmac::Cmac;
+use super::ctr::{Ctr, Split};
+
+
+/// EAX with 128-bit nonces and `T` byte tags, 1 to 16. The header is the
+/// associated data of the `aead` traits, authenticated but not encrypted.
+#[derive(Clone)]
+pub struct Eax<C, T = U16> {
+    mac: Cmac<C>,
+    tag: PhantomData<T>,
+}
+
+impl<C: BlockEncrypt<BlockSize = U16> + Clone, T: ArrayLength<u8>> From<C> for Eax<C, T> {
+    fn from(cipher: C) -> Self {
+        let () = Self::VALID_TAG;
+        Eax { mac: Cmac::from(cipher), tag: PhantomData }
+    }
+}
+
+impl<C: KeySizeUser, T> KeySizeUser for Eax<C, T> {
+    type KeySize = C::KeySize;
+}
+
+impl<C: BlockEncrypt<BlockSize = U16> + Clone + KeyInit, T: ArrayLength<u8>> KeyInit for Eax<C, T> {
+    fn new(key: &Key<Self>) -> Self {
+        C::new(key).into()
+    }
+}
+
+impl<C, T: ArrayLength<u8>> AeadCore for Eax<C, T> {
+    type NonceSize = U16;
+    type TagSize = T;
+    type CiphertextOverhead = U0;
+}
+
+impl<C, T> AeadInPlace for Eax<C, T>
+where
+    C: BlockEncrypt<BlockSize = U16> + Clone,
+    T: ArrayLength<u8>,
+{
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> aead::Result<Tag<Self>> {
+        let n = self.omac(0, nonce);
+        self.keystream(&n)
+            .try_apply_keystream(buffer)
+            .map_err(|_| aead::Error)?;
+        Ok(self.tag(n, associated_data, buffer))
+    }
+
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &Tag<Self>,
+    ) -> aead::Result<()> {
+        let n = self.omac(0, nonce);
+        let expected = self.tag(n, associated_data, buffer);
+        if expected.ct_eq(tag).into() {
+            self.keystream(&n)
+                .try_apply_keystream(buffer)
+                .map_err(|_| aead::Error)
+        } else {
+            Err(aead::Error)
+        }
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16> + Clone, T: ArrayLength<u8>> Eax<C, T> {
+
+    const VALID_TAG: () = assert!(T::USIZE >= 1 && T::USIZE <= 16, "EAX tags are 1 to 16 bytes");
+
+    /// OMAC^t(data), CMAC of `data` behind a block holding the domain `t`.
+    fn omac(&self, t: u8, data: &[u8]) -> [u8; 16] {
+        let mut domain = [0; 16];
+        domain[15] = t;
+        let mut mac = self.mac.clone();
+        mac.update(&domain);
+        mac.update(data);
+        mac.finalize().into_bytes().into()
+    }
+
+    fn tag(&self, n: [u8; 16], header: &[u8], ciphertext: &[u8]) -> Tag<Self> {
+        let h = u128::from_be_bytes(self.omac(1, header));
+        let c = u128::from_be_bytes(self.omac(2, ciphertext));
+        let full = u128::from_be_bytes(n) ^ h ^ c;
+        GenericArray::clone_from_slice(&full.to_be_bytes()[..T::USIZE])
+    }
+
+    // the whole block is the counter; running off its end would need about
+    // 2^128 - N blocks, which fails instead of wrapping
+    fn keystream(&self, n: &[u8; 16]) -> Ctr<&C> {
+        Ctr::new(self.mac.cipher(), Split::Counter128, n)
+    }
+}
+
+
+#[cfg(test)]
+use hex_literal::hex;
+
+#[cfg(test)]
+struct Vector {
+    key: &'static [u8],
+    nonce: &'static [u8],
+    header: &'static [u8],
+    msg: &'static [u8],
+    // ciphertext || tag
+    sealed: &'static [u8],
+}
+
+// The test vectors of the EAX paper, Bellare, Rogaway and Wagner
+#[cfg(test)]
+const AES128_VECTORS: &[Vector] = &[
+    Vector {
+        key: &hex!("233952dee4d5ed5f9b9c6d6ff80ff478"),
+        nonce: &hex!("62ec67f9c3a4a407fcb2a8c49031a8b3"),
+        header: &hex!("6bfb914fd07eae6b"),
+        msg: &[],
+        sealed: &hex!("e037830e8389f27b025a2d6527e79d01"),
+    },
+    Vector {
+        key: &hex!("91945d3f4dcbee0bf45ef52255f095a4"),
+        nonce: &hex!("becaf043b0a23d843194ba972c66debd"),
+        header: &hex!("fa3bfd4806eb53fa"),
+        msg: &hex!("f7fb"),
+        sealed: &hex!("19dd5c4c9331049d0bdab0277408f67967e5"),
+    },
+    Vector {
+        key: &hex!("01f74ad64077f2e704c0f60ada3dd523"),
+        nonce: &hex!("70c3db4f0d26368400a10ed05d2bff5e"),
+        header: &hex!("234a3463c1264ac6"),
+        msg: &hex!("1a47cb4933"),
+        sealed: &hex!("d851d5bae03a59f238a23e39199dc9266626c40f80"),
+    },
+    Vector {
+        key: &hex!("d07cf6cbb7f313bdde66b727afd3c5e8"),
+        nonce: &hex!("8408dfff3c1a2b1292dc199e46b7d617"),
+        header: &hex!("33cce2eabff5a79d"),
+        msg: &hex!("481c9e39b1"),
+        sealed: &hex!("632a9d131ad4c168a4225d8e1ff755939974a7bede"),
+    },
+    Vector {
+        key: &hex!("35b6d0580005bbc12b0587124557d2c2"),
+        nonce: &hex!("fdb6b06676eedc5c61d74276e1f8e816"),
+        header: &hex!("aeb96eaebe2970e9"),
+        msg: &hex!("40d0c07da5e4"),
+        sealed: &hex!("071dfe16c675cb0677e536f73afe6a14b74ee49844dd"),
+    },
+    Vector {
+        key: &hex!("bd8e6e11475e60b268784c38c62feb22"),
+        nonce: &hex!("6eac5c93072d8e8513f750935e46da1b"),
+        header: &hex!("d4482d1ca78dce0f"),
+        msg: &hex!("4de3b35c3fc039245bd1fb7d"),
+        sealed: &hex!("835bb4f15d743e350e728414abb8644fd6ccb86947c5e10590210a4f"),
+    },
+    Vector {
+        key: &hex!("7c77d6e813bed5ac98baa417477a2e7d"),
+        nonce: &hex!("1a8c98dcd73d38393b2bf1569deefc19"),
+        header: &hex!("65d2017990d62528"),
+        msg: &hex!("8b0a79306c9ce7ed99dae4f87f8dd61636"),
+        sealed: &hex!("02083e3979da014812f59f11d52630da30137327d10649b0aa6e1c181db617d7f2"),
+    },
+    Vector {
+        key: &hex!("5fff20cafab119ca2fc73549e20f5b0d"),
+        nonce: &hex!("dde59b97d722156d4d9aff2bc7559826"),
+        header: &hex!("54b9f04e6a09189a"),
+        msg: &hex!("1bda122bce8a8dbaf1877d962b8592dd2d56"),
+        sealed: &hex!("2ec47b2c4954a489afc7ba4897edcdae8cc33b60450599bd02c96382902aef7f832a"),
+    },
+    Vector {
+        key: &hex!("a4a4782bcffd3ec5e7ef6d8c34a56123"),
+        nonce: &hex!("b781fcf2f75fa5a8de97a9ca48e522ec"),
+        header: &hex!("899a175897561d7e"),
+        msg: &hex!("6cf36720872b8513f6eab1a8a44438d5ef11"),
+        sealed: &hex!("0de18fd0fdd91e7af19f1d8ee8733938b1e8e7f6d2231618102fdb7fe55ff1991700"),
+    },
+    Vector {
+        key: &hex!("8395fcf1e95bebd697bd010bc766aac3"),
+        nonce: &hex!("22e7add93cfc6393c57ec0b3c17d6b44"),
+        header: &hex!("126735fcc320d25a"),
+        msg: &hex!("ca40d7446e545ffaed3bd12a740a659ffbbb3ceab7"),
+        sealed: &hex!("cb8920f87a6c75cff39627b56e3ed197c552d295a7cfc46afc253b4652b1af3795b124ab6e"),
+    },
+];
+
+#[test]
+fn test_aes_vectors(){
+    use aes::Aes128;
+
+
+    for &Vector { key, nonce, header, msg, sealed } in AES128_VECTORS {
+        let eax = Eax::<Aes128>::new_from_slice(key).unwrap();
+        let nonce = Nonce::<Eax<Aes128>>::clone_from_slice(nonce);
+        let (ciphertext, tag) = sealed.split_at(msg.len());
+
+        let mut buffer = [0; 21];
+        let buffer = &mut buffer[..msg.len()];
+        buffer.copy_from_slice(msg);
+        let t = eax.encrypt_in_place_detached(&nonce, header, buffer).unwrap();
+        assert_eq!(buffer, ciphertext);
+        assert_eq!(t.as_slice(), tag);
+
+        eax.decrypt_in_place_detached(&nonce, header, buffer, &t).unwrap();
+        assert_eq!(buffer, msg);
+
+        // a truncated tag is a prefix of the full one
+        let eax = Eax::<Aes128, aead::consts::U4>::new_from_slice(key).unwrap();
+        buffer.copy_from_slice(msg);
+        let t = eax.encrypt_in_place_detached(&nonce, header, buffer).unwrap();
+        assert_eq!(t.as_slice(), &tag[..4]);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_tekton(){
+    use crate::imp::{b128::Tekton128, b256::Tekton256};
+    use aead::{Aead, Payload, consts::U8};
+    use rand::Rng;
+
+    let key: [u8; 16] = rand::thread_rng().gen();
+    super::check_aead(&Eax::<Tekton128>::new(&key.into()));
+    super::check_aead(&Eax::<Tekton128, U8>::new(&key.into()));
+    super::check_aead(&Eax::<Tekton256>::new(&rand::thread_rng().gen::<[u8; 32]>().into()));
+
+    // the header is only authenticated, it changes the tag and not the ciphertext
+    let eax = Eax::<Tekton128>::new(&key.into());
+    let nonce = Nonce::<Eax<Tekton128>>::from(rand::thread_rng().gen::<[u8; 16]>());
+    let msg = [0x5a; 40];
+    let sealed = eax.encrypt(&nonce, Payload { msg: &msg, aad: b"header" }).unwrap();
+    let other = eax.encrypt(&nonce, Payload { msg: &msg, aad: b"headers" }).unwrap();
+    let empty = eax.encrypt(&nonce, &msg[..]).unwrap();
+    assert_eq!(sealed[..40], other[..40]);
+    assert_eq!(sealed[..40], empty[..40]);
+    assert_ne!(sealed[40..], other[40..]);
+    assert_ne!(sealed[40..], empty[40..]);
+    assert!(eax.decrypt(&nonce, Payload { msg: &sealed, aad: b"" }).is_err());
+}
@@ -0,0 +1,119 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use cipher::{consts::U16, BlockDecrypt, BlockEncrypt};
+
+use super::{blocks_mut, padding, Error};
+
+
+/// Encrypts `data` in place, which must be a whole number of blocks. Identical
+/// plaintext blocks give identical ciphertext blocks, prefer `cbc` or `ctr`.
+pub fn encrypt<C>(cipher: &C, data: &mut [u8]) -> Result<(), Error>
+where
+    C: BlockEncrypt<BlockSize = U16>,
+{
+    cipher.encrypt_blocks(blocks_mut(data)?);
+    Ok(())
+}
+
+pub fn decrypt<C>(cipher: &C, data: &mut [u8]) -> Result<(), Error>
+where
+    C: BlockDecrypt<BlockSize = U16>,
+{
+    cipher.decrypt_blocks(blocks_mut(data)?);
+    Ok(())
+}
+
+/// Pads the first `len` bytes of `buf` and encrypts them in place, `buf` needs
+/// room for `padding::padded_len(len)` bytes.
+pub fn encrypt_padded<'a, C>(cipher: &C, buf: &'a mut [u8], len: usize) -> Result<&'a [u8], Error>
+where
+    C: BlockEncrypt<BlockSize = U16>,
+{
+    let padded = padding::pad(buf, len)?;
+    encrypt(cipher, padded)?;
+    Ok(padded)
+}
+
+/// Decrypts `buf` in place and returns the plaintext without padding.
+pub fn decrypt_padded<'a, C>(cipher: &C, buf: &'a mut [u8]) -> Result<&'a [u8], Error>
+where
+    C: BlockDecrypt<BlockSize = U16>,
+{
+    decrypt(cipher, buf)?;
+    padding::unpad(buf)
+}
+
+#[cfg(feature = "alloc")]
+pub fn encrypt_vec<C>(cipher: &C, data: &[u8]) -> Vec<u8>
+where
+    C: BlockEncrypt<BlockSize = U16>,
+{
+    let mut buf = padding::pad_vec(data);
+    encrypt(cipher, &mut buf).expect("padded to whole blocks");
+    buf
+}
+
+#[cfg(feature = "alloc")]
+pub fn decrypt_vec<C>(cipher: &C, data: &[u8]) -> Result<Vec<u8>, Error>
+where
+    C: BlockDecrypt<BlockSize = U16>,
+{
+    let mut buf = data.to_vec();
+    let len = decrypt_padded(cipher, &mut buf)?.len();
+    buf.truncate(len);
+    Ok(buf)
+}
+
+
+#[cfg(test)]
+use hex_literal::hex;
+
+// NIST SP 800-38A, F.1.1 and F.1.2
+#[test]
+fn test_aes_vectors(){
+    use aes::Aes128;
+    use cipher::KeyInit;
+
+    let aes = Aes128::new(&hex!("2b7e151628aed2a6abf7158809cf4f3c").into());
+    let plain = hex!(
+        "6bc1bee22e409f96e93d7e117393172a ae2d8a571e03ac9c9eb76fac45af8e51"
+        "30c81c46a35ce411e5fbc1191a0a52ef f69f2445df4f9b17ad2b417be66c3710"
+    );
+    let expected = hex!(
+        "3ad77bb40d7a3660a89ecaf32466ef97 f5d3d58503b9699de785895a96fdbaaf"
+        "43b1cd7f598ece23881b00e3ed030688 7b0c785e27e8ad3f8223207104725dd4"
+    );
+
+    let mut data = plain;
+    encrypt(&aes, &mut data).unwrap();
+    assert_eq!(data, expected);
+
+    decrypt(&aes, &mut data).unwrap();
+    assert_eq!(data, plain);
+
+    assert_eq!(encrypt(&aes, &mut [0; 17]), Err(Error::InvalidLength));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_padded_roundtrip(){
+    use crate::imp::{b128::Tekton128, Flags};
+    use rand::Rng;
+
+    let tekton = Tekton128::new(rand::thread_rng().gen(), Flags::default());
+
+    for len in 0..100 {
+        let data: Vec<u8> = (0..len).map(|_| rand::thread_rng().gen()).collect();
+
+        let enc = encrypt_vec(&tekton, &data);
+        assert_eq!(enc.len(), padding::padded_len(len));
+        assert_eq!(decrypt_vec(&tekton, &enc).unwrap(), data);
+
+        let mut buf = [0; 112];
+        buf[..len].copy_from_slice(&data);
+        assert_eq!(encrypt_padded(&tekton, &mut buf, len).unwrap(), &enc[..]);
+        let n = enc.len();
+        assert_eq!(decrypt_padded(&tekton, &mut buf[..n]).unwrap(), &data[..]);
+    }
+}
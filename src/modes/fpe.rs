@@ -0,0 +1,204 @@
+use alloc::{string::String, vec::Vec};
+
+use super::Error;
+
+pub mod ff1;
+
+pub mod ff3_1;
+
+
+/// Largest radix, numerals are `u16`.
+pub const MAX_RADIX: u32 = 1 << 16;
+
+/// Least number of values the domain of a numeral string must have.
+const MIN_DOMAIN: u64 = 1_000_000;
+
+pub const DECIMAL: &str = "0123456789";
+
+/// Digits then lowercase letters, the radix 36 alphabet of the NIST samples.
+pub const ALPHANUMERIC: &str = "0123456789abcdefghijklmnopqrstuvwxyz";
+
+
+/// Format-preserving encryption (NIST SP 800-38G) of numeral strings in a
+/// fixed radix. Numerals are in `0..radix`, the most significant first, and
+/// the output has the length and radix of the input.
+pub trait Fpe {
+    type Tweak: ?Sized;
+
+    fn radix(&self) -> u32;
+
+    fn encrypt(&self, tweak: &Self::Tweak, numerals: &mut [u16]) -> Result<(), Error>;
+
+    fn decrypt(&self, tweak: &Self::Tweak, numerals: &mut [u16]) -> Result<(), Error>;
+}
+
+
+/// Characters standing for the numerals `0..radix`, in order.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct Alphabet {
+    chars: Vec<char>,
+    // (char, numeral) sorted by char
+    index: Vec<(char, u16)>,
+}
+
+impl Alphabet {
+
+    /// Fails with `Error::InvalidRadix` unless `chars` has 2 to `MAX_RADIX` distinct characters.
+    pub fn new(chars: &str) -> Result<Self, Error> {
+        let chars: Vec<char> = chars.chars().collect();
+        if chars.len() < 2 || chars.len() > MAX_RADIX as usize {
+            return Err(Error::InvalidRadix);
+        }
+        let mut index: Vec<(char, u16)> = chars.iter().enumerate().map(|(i, &c)| (c, i as u16)).collect();
+        index.sort_unstable();
+        if index.windows(2).any(|w| w[0].0 == w[1].0) {
+            return Err(Error::InvalidRadix);
+        }
+        Ok(Alphabet { chars, index })
+    }
+
+    pub fn radix(&self) -> u32 {
+        self.chars.len() as u32
+    }
+
+    pub fn numerals(&self, s: &str) -> Result<Vec<u16>, Error> {
+        s.chars().map(|c| {
+            self.index.binary_search_by_key(&c, |&(c, _)| c)
+                .map(|i| self.index[i].1)
+                .map_err(|_| Error::InvalidNumeral)
+        }).collect()
+    }
+
+    pub fn string(&self, numerals: &[u16]) -> Result<String, Error> {
+        numerals.iter().map(|&x| self.chars.get(x as usize).ok_or(Error::InvalidNumeral)).collect()
+    }
+
+    /// Encrypts `s` into a string of the same length over this alphabet, the
+    /// radix of `fpe` must be the size of the alphabet.
+    pub fn encrypt<F: Fpe>(&self, fpe: &F, tweak: &F::Tweak, s: &str) -> Result<String, Error> {
+        let mut numerals = self.check(fpe, s)?;
+        fpe.encrypt(tweak, &mut numerals)?;
+        self.string(&numerals)
+    }
+
+    pub fn decrypt<F: Fpe>(&self, fpe: &F, tweak: &F::Tweak, s: &str) -> Result<String, Error> {
+        let mut numerals = self.check(fpe, s)?;
+        fpe.decrypt(tweak, &mut numerals)?;
+        self.string(&numerals)
+    }
+
+    fn check<F: Fpe>(&self, fpe: &F, s: &str) -> Result<Vec<u16>, Error> {
+        if fpe.radix() != self.radix() {
+            return Err(Error::InvalidRadix);
+        }
+        self.numerals(s)
+    }
+}
+
+
+fn check_radix(radix: u32) -> Result<(), Error> {
+    if !(2..=MAX_RADIX).contains(&radix) {
+        return Err(Error::InvalidRadix);
+    }
+    Ok(())
+}
+
+/// Shortest numeral string with at least `MIN_DOMAIN` values, and at least 2 numerals.
+fn min_len(radix: u32) -> usize {
+    let mut len = 1;
+    let mut domain = radix as u64;
+    while domain < MIN_DOMAIN {
+        domain *= radix as u64;
+        len += 1;
+    }
+    len.max(2)
+}
+
+fn check_numerals(radix: u32, numerals: &[u16], max_len: usize) -> Result<(), Error> {
+    if numerals.len() < min_len(radix) || numerals.len() > max_len {
+        return Err(Error::InvalidLength);
+    }
+    if numerals.iter().any(|&x| x as u32 >= radix) {
+        return Err(Error::InvalidNumeral);
+    }
+    Ok(())
+}
+
+/// Writes NUM_radix of `numerals`, most significant first, as big-endian
+/// bytes filling `out`. The value must fit.
+fn num(numerals: impl Iterator<Item = u16>, radix: u32, out: &mut [u8]) {
+    out.fill(0);
+    for x in numerals {
+        let mut carry = x as u32;
+        for byte in out.iter_mut().rev() {
+            let t = *byte as u32 * radix + carry;
+            *byte = t as u8;
+            carry = t >> 8;
+        }
+    }
+}
+
+/// Adds, or subtracts, the big-endian `y` to the numeral string modulo
+/// radix^m, where m is the number of numerals, taken least significant
+/// first. `y` is used up as scratch space.
+fn add_mod<'a>(numerals: impl Iterator<Item = &'a mut u16>, y: &mut [u8], radix: u32, subtract: bool) {
+    let mut carry = 0;
+    for x in numerals {
+        // the next numeral of y, y / radix stays in place
+        let mut r = 0;
+        for byte in y.iter_mut() {
+            let t = (r << 8) | *byte as u32;
+            *byte = (t / radix) as u8;
+            r = t % radix;
+        }
+        let t = if subtract {
+            *x as u32 + radix - r - carry
+        } else {
+            *x as u32 + r + carry
+        };
+        *x = (t % radix) as u16;
+        carry = if subtract { 1 - t / radix } else { t / radix };
+    }
+}
+
+
+#[test]
+fn test_alphabet(){
+    let digits = Alphabet::new(DECIMAL).unwrap();
+    assert_eq!(digits.radix(), 10);
+    assert_eq!(digits.numerals("4111").unwrap(), [4, 1, 1, 1]);
+    assert_eq!(digits.string(&[0, 9, 5]).unwrap(), "095");
+    assert_eq!(digits.numerals("41a1"), Err(Error::InvalidNumeral));
+    assert_eq!(digits.string(&[10]), Err(Error::InvalidNumeral));
+
+    let greek = Alphabet::new("αβγδ").unwrap();
+    assert_eq!(greek.radix(), 4);
+    assert_eq!(greek.string(&greek.numerals("δαγ").unwrap()).unwrap(), "δαγ");
+
+    assert_eq!(Alphabet::new("0"), Err(Error::InvalidRadix));
+    assert_eq!(Alphabet::new("01230"), Err(Error::InvalidRadix));
+
+    assert_eq!(min_len(2), 20);
+    assert_eq!(min_len(10), 6);
+    assert_eq!(min_len(36), 4);
+    assert_eq!(min_len(MAX_RADIX), 2);
+}
+
+#[test]
+fn test_arithmetic(){
+    let mut out = [0; 4];
+    num([1, 2, 3, 4, 5].into_iter(), 10, &mut out);
+    assert_eq!(u32::from_be_bytes(out), 12345);
+
+    // 987 + 45 = 1032 = 032 mod 1000, and back
+    let mut x = [9, 8, 7];
+    add_mod(x.iter_mut().rev(), &mut [0, 45], 10, false);
+    assert_eq!(x, [0, 3, 2]);
+    add_mod(x.iter_mut().rev(), &mut [0, 45], 10, true);
+    assert_eq!(x, [9, 8, 7]);
+
+    // y is reduced modulo radix^m
+    let mut x = [0, 0];
+    add_mod(x.iter_mut().rev(), &mut 1_000_123u32.to_be_bytes(), 10, true);
+    assert_eq!(x, [7, 7]);
+}
@@ -0,0 +1,251 @@
+use alloc::{vec, vec::Vec};
+use core::mem;
+
+use cipher::{consts::U16, BlockEncrypt, KeyInit};
+use zeroize::Zeroize;
+
+use super::{add_mod, check_numerals, check_radix, num, Fpe};
+use super::super::{xor, Block, Error, BLOCK_SIZE};
+
+
+const ROUNDS: u8 = 10;
+
+
+/// FF1 (NIST SP 800-38G) over a 128-bit block cipher, with tweaks of any
+/// length up to 2^32 - 1 bytes and numeral strings of up to 2^32 - 1
+/// numerals.
+#[derive(Clone)]
+pub struct Ff1<C> {
+    cipher: C,
+    radix: u32,
+}
+
+impl<C> Ff1<C> {
+
+    pub fn new(cipher: C, radix: u32) -> Result<Self, Error> {
+        check_radix(radix)?;
+        Ok(Ff1 { cipher, radix })
+    }
+}
+
+impl<C: KeyInit> Ff1<C> {
+
+    pub fn new_from_slice(key: &[u8], radix: u32) -> Result<Self, Error> {
+        Ff1::new(C::new_from_slice(key).map_err(|_| Error::InvalidLength)?, radix)
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> Fpe for Ff1<C> {
+    type Tweak = [u8];
+
+    fn radix(&self) -> u32 {
+        self.radix
+    }
+
+    fn encrypt(&self, tweak: &[u8], numerals: &mut [u16]) -> Result<(), Error> {
+        self.feistel(tweak, numerals, false)
+    }
+
+    fn decrypt(&self, tweak: &[u8], numerals: &mut [u16]) -> Result<(), Error> {
+        self.feistel(tweak, numerals, true)
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> Ff1<C> {
+
+    fn feistel(&self, tweak: &[u8], numerals: &mut [u16], decrypt: bool) -> Result<(), Error> {
+        check_numerals(self.radix, numerals, u32::MAX as usize)?;
+        if tweak.len() > u32::MAX as usize {
+            return Err(Error::InvalidLength);
+        }
+        let n = numerals.len();
+        let u = n / 2;
+        let v = n - u;
+        let b = byte_len(self.radix, v);
+        let d = 4 * b.div_ceil(4) + 4;
+
+        let mut p = [0; 16];
+        p[..3].copy_from_slice(&[1, 2, 1]);
+        p[3..6].copy_from_slice(&self.radix.to_be_bytes()[1..]);
+        p[6] = 10;
+        p[7] = u as u8;
+        p[8..12].copy_from_slice(&(n as u32).to_be_bytes());
+        p[12..].copy_from_slice(&(tweak.len() as u32).to_be_bytes());
+        let mut prefix = Block::from(p);
+        self.cipher.encrypt_block(&mut prefix);
+
+        // T || 0^pad || i || NUM(B), only the last b + 1 bytes change between rounds
+        let pad = (BLOCK_SIZE - (tweak.len() + b + 1) % BLOCK_SIZE) % BLOCK_SIZE;
+        let mut q = vec![0; tweak.len() + pad + 1 + b];
+        q[..tweak.len()].copy_from_slice(tweak);
+        let mut s = vec![Block::default(); d.div_ceil(BLOCK_SIZE)];
+        let mut y = vec![0; d];
+
+        let mut a = numerals[..u].to_vec();
+        let mut bb = numerals[u..].to_vec();
+        for i in 0..ROUNDS {
+            let i = if decrypt { ROUNDS - 1 - i } else { i };
+            let (src, dst) = if decrypt { (&a, &mut bb) } else { (&bb, &mut a) };
+
+            let (round, num_src) = q[tweak.len() + pad..].split_at_mut(1);
+            round[0] = i;
+            num(src.iter().copied(), self.radix, num_src);
+
+            // S = R || E(R ^ 1) || E(R ^ 2) ..., R the CBC-MAC of P || Q
+            let mut r = prefix;
+            for block in q.chunks(BLOCK_SIZE) {
+                xor(&mut r, block);
+                self.cipher.encrypt_block(&mut r);
+            }
+            for (j, block) in s.iter_mut().enumerate() {
+                *block = (u128::from_be_bytes(r.into()) ^ j as u128).to_be_bytes().into();
+            }
+            s[0] = r;
+            self.cipher.encrypt_blocks(&mut s[1..]);
+            for (part, block) in y.chunks_mut(BLOCK_SIZE).zip(&s) {
+                part.copy_from_slice(&block[..part.len()]);
+            }
+
+            // C = A + y, or B - y, modulo radix^m with m the length of that half
+            add_mod(dst.iter_mut().rev(), &mut y, self.radix, decrypt);
+            mem::swap(&mut a, &mut bb);
+        }
+
+        numerals[..u].copy_from_slice(&a);
+        numerals[u..].copy_from_slice(&bb);
+
+        q.zeroize();
+        for block in &mut s {
+            block.as_mut_slice().zeroize();
+        }
+        a.zeroize();
+        bb.zeroize();
+        Ok(())
+    }
+}
+
+
+/// Bytes of radix^len - 1, that is ceil(ceil(len * log2(radix)) / 8).
+fn byte_len(radix: u32, len: usize) -> usize {
+    // little-endian radix^len
+    let mut x: Vec<u8> = vec![1];
+    for _ in 0..len {
+        let mut carry = 0;
+        for byte in &mut x {
+            let t = *byte as u32 * radix + carry;
+            *byte = t as u8;
+            carry = t >> 8;
+        }
+        while carry > 0 {
+            x.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+    // minus one only shortens an exact power of 256
+    let power = x[..x.len() - 1].iter().all(|&byte| byte == 0) && x[x.len() - 1] == 1;
+    x.len() - power as usize
+}
+
+
+#[cfg(test)]
+use super::{Alphabet, ALPHANUMERIC, DECIMAL};
+
+#[cfg(test)]
+use hex_literal::hex;
+
+// https://csrc.nist.gov/CSRC/media/Projects/Cryptographic-Standards-and-Guidelines/documents/examples/FF1samples.pdf
+#[test]
+fn test_nist_vectors(){
+    use aes::{Aes128, Aes192, Aes256};
+
+    let key = hex!("2b7e151628aed2a6abf7158809cf4f3c ef4359d8d580aa4f7f036d6f04fc6a94");
+    let digits = Alphabet::new(DECIMAL).unwrap();
+    let alphanumeric = Alphabet::new(ALPHANUMERIC).unwrap();
+
+    fn check<C: BlockEncrypt<BlockSize = U16> + KeyInit>(
+        key: &[u8], alphabet: &Alphabet, tweak: &[u8], plain: &str, cipher: &str,
+    ) {
+        let ff1 = Ff1::<C>::new_from_slice(key, alphabet.radix()).unwrap();
+        assert_eq!(alphabet.encrypt(&ff1, tweak, plain).unwrap(), cipher);
+        assert_eq!(alphabet.decrypt(&ff1, tweak, cipher).unwrap(), plain);
+    }
+
+    let tweak = hex!("39383736353433323130");
+    let tweak36 = hex!("3737373770717273373737");
+
+    check::<Aes128>(&key[..16], &digits, &[], "0123456789", "2433477484");
+    check::<Aes128>(&key[..16], &digits, &tweak, "0123456789", "6124200773");
+    check::<Aes128>(&key[..16], &alphanumeric, &tweak36, "0123456789abcdefghi", "a9tv40mll9kdu509eum");
+    check::<Aes192>(&key[..24], &digits, &[], "0123456789", "2830668132");
+    check::<Aes192>(&key[..24], &digits, &tweak, "0123456789", "2496655549");
+    check::<Aes192>(&key[..24], &alphanumeric, &tweak36, "0123456789abcdefghi", "xbj3kv35jrawxv32ysr");
+    check::<Aes256>(&key, &digits, &[], "0123456789", "6657667009");
+    check::<Aes256>(&key, &digits, &tweak, "0123456789", "1001623463");
+    check::<Aes256>(&key, &alphanumeric, &tweak36, "0123456789abcdefghi", "xs8a0azh2avyalyzuwd");
+}
+
+#[test]
+fn test_tekton(){
+    use crate::imp::b128::Tekton128;
+    use rand::Rng;
+
+    let key: [u8; 16] = rand::thread_rng().gen();
+    let tweak: [u8; 20] = rand::thread_rng().gen();
+
+    for radix in [2, 10, 26, 36, 255, 256, 257, 1000, super::MAX_RADIX] {
+        let ff1 = Ff1::<Tekton128>::new_from_slice(&key, radix).unwrap();
+        let min = super::min_len(radix);
+
+        for len in [min, min + 1, 17, 64, 255] {
+            let len = len.max(min);
+            let plain: Vec<u16> = (0..len).map(|_| rand::thread_rng().gen_range(0..radix) as u16).collect();
+
+            for tweak in [&tweak[..0], &tweak[..7], &tweak[..]] {
+                let mut enc = plain.clone();
+                ff1.encrypt(tweak, &mut enc).unwrap();
+                assert!(enc.iter().all(|&x| (x as u32) < radix));
+
+                let mut other = plain.clone();
+                let mut other_tweak = tweak.to_vec();
+                other_tweak.push(0);
+                ff1.encrypt(&other_tweak, &mut other).unwrap();
+                assert_ne!(other, enc);
+
+                ff1.decrypt(tweak, &mut enc).unwrap();
+                assert_eq!(enc, plain);
+            }
+        }
+
+        let mut short = vec![0; min - 1];
+        assert_eq!(ff1.encrypt(&[], &mut short), Err(Error::InvalidLength));
+        if radix < super::MAX_RADIX {
+            let mut high = vec![radix as u16; min];
+            assert_eq!(ff1.encrypt(&[], &mut high), Err(Error::InvalidNumeral));
+        }
+    }
+
+    assert!(Ff1::new(Tekton128::new(key, Default::default()), 1).is_err());
+    assert!(Ff1::new(Tekton128::new(key, Default::default()), super::MAX_RADIX + 1).is_err());
+
+    // a card number stays 16 digits
+    let digits = Alphabet::new(DECIMAL).unwrap();
+    let ff1 = Ff1::<Tekton128>::new_from_slice(&key, 10).unwrap();
+    let token = digits.encrypt(&ff1, b"merchant 42", "4111111111111111").unwrap();
+    assert_eq!(token.len(), 16);
+    assert!(token.bytes().all(|c| c.is_ascii_digit()));
+    assert_eq!(digits.decrypt(&ff1, b"merchant 42", &token).unwrap(), "4111111111111111");
+    assert_eq!(digits.encrypt(&ff1, b"", "41111"), Err(Error::InvalidLength));
+
+    let wrong = Alphabet::new(ALPHANUMERIC).unwrap();
+    assert_eq!(wrong.encrypt(&ff1, b"", "4111111111111111"), Err(Error::InvalidRadix));
+}
+
+#[test]
+fn test_byte_len(){
+    assert_eq!(byte_len(10, 5), 3);
+    assert_eq!(byte_len(2, 8), 1);
+    assert_eq!(byte_len(2, 9), 2);
+    assert_eq!(byte_len(256, 3), 3);
+    assert_eq!(byte_len(257, 3), 4);
+    assert_eq!(byte_len(1 << 16, 5), 10);
+}
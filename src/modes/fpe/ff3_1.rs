@@ -0,0 +1,235 @@
+use alloc::vec::Vec;
+use core::mem;
+
+use cipher::{consts::U16, BlockEncrypt, KeyInit};
+use zeroize::Zeroize;
+
+use super::{add_mod, check_numerals, check_radix, num, Fpe};
+use super::super::{Block, Error};
+
+
+const ROUNDS: u32 = 8;
+
+
+/// FF3-1 (NIST SP 800-38G Rev. 1) over a 128-bit block cipher, with 56-bit
+/// tweaks. The standard keys the cipher with the byte-reversed key, which
+/// `new_from_slice` does, `new` takes the cipher as it is.
+#[derive(Clone)]
+pub struct Ff3_1<C> {
+    cipher: C,
+    radix: u32,
+}
+
+impl<C> Ff3_1<C> {
+
+    pub fn new(cipher: C, radix: u32) -> Result<Self, Error> {
+        check_radix(radix)?;
+        Ok(Ff3_1 { cipher, radix })
+    }
+
+    /// Longest numeral string, 2 * floor(log_radix(2^96)).
+    pub fn max_len(&self) -> usize {
+        let mut len = 0;
+        let mut domain = 1u128;
+        while let Some(d) = domain.checked_mul(self.radix as u128).filter(|&d| d <= 1 << 96) {
+            domain = d;
+            len += 1;
+        }
+        2 * len
+    }
+}
+
+impl<C: KeyInit> Ff3_1<C> {
+
+    pub fn new_from_slice(key: &[u8], radix: u32) -> Result<Self, Error> {
+        let mut reversed: Vec<u8> = key.iter().rev().copied().collect();
+        let cipher = C::new_from_slice(&reversed).map_err(|_| Error::InvalidLength);
+        reversed.zeroize();
+        Ff3_1::new(cipher?, radix)
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> Fpe for Ff3_1<C> {
+    type Tweak = [u8; 7];
+
+    fn radix(&self) -> u32 {
+        self.radix
+    }
+
+    fn encrypt(&self, tweak: &[u8; 7], numerals: &mut [u16]) -> Result<(), Error> {
+        let (tl, tr) = split_tweak(tweak);
+        self.feistel(tl, tr, numerals, false)
+    }
+
+    fn decrypt(&self, tweak: &[u8; 7], numerals: &mut [u16]) -> Result<(), Error> {
+        let (tl, tr) = split_tweak(tweak);
+        self.feistel(tl, tr, numerals, true)
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> Ff3_1<C> {
+
+    /// The FF3 rounds, FF3-1 only differs in how the tweak halves are made.
+    fn feistel(&self, tl: [u8; 4], tr: [u8; 4], numerals: &mut [u16], decrypt: bool) -> Result<(), Error> {
+        check_numerals(self.radix, numerals, self.max_len())?;
+        let u = numerals.len().div_ceil(2);
+
+        // halves are read least significant first, REV in the standard
+        let mut a = numerals[..u].to_vec();
+        let mut b = numerals[u..].to_vec();
+        for i in 0..ROUNDS {
+            let i = if decrypt { ROUNDS - 1 - i } else { i };
+            let (src, dst) = if decrypt { (&a, &mut b) } else { (&b, &mut a) };
+            let w = if i % 2 == 0 { tr } else { tl };
+
+            let mut p = [0; 16];
+            p[..4].copy_from_slice(&(u32::from_be_bytes(w) ^ i).to_be_bytes());
+            num(src.iter().rev().copied(), self.radix, &mut p[4..]);
+
+            // S = REVB(E(REVB(P)))
+            p.reverse();
+            let mut s = Block::from(p);
+            self.cipher.encrypt_block(&mut s);
+            s.reverse();
+
+            add_mod(dst.iter_mut(), &mut s, self.radix, decrypt);
+            mem::swap(&mut a, &mut b);
+            p.zeroize();
+            s.as_mut_slice().zeroize();
+        }
+
+        numerals[..u].copy_from_slice(&a);
+        numerals[u..].copy_from_slice(&b);
+        a.zeroize();
+        b.zeroize();
+        Ok(())
+    }
+}
+
+
+/// T_L is the first 28 bits of the tweak and T_R the last 24 followed by
+/// the 4 in between, each padded with 4 zero bits.
+fn split_tweak(t: &[u8; 7]) -> ([u8; 4], [u8; 4]) {
+    ([t[0], t[1], t[2], t[3] & 0xf0], [t[4], t[5], t[6], t[3] << 4])
+}
+
+
+#[cfg(test)]
+use super::{Alphabet, DECIMAL};
+
+#[cfg(test)]
+use hex_literal::hex;
+
+// FF3 with 64-bit tweaks, the rounds FF3-1 keeps
+// https://csrc.nist.gov/CSRC/media/Projects/Cryptographic-Standards-and-Guidelines/documents/examples/FF3samples.pdf
+#[test]
+fn test_nist_ff3_vectors(){
+    use aes::{Aes128, Aes192, Aes256};
+
+    let key = hex!("ef4359d8d580aa4f7f036d6f04fc6a94 2b7e151628aed2a6abf7158809cf4f3c");
+    let digits = Alphabet::new(DECIMAL).unwrap();
+    let radix26 = Alphabet::new("0123456789abcdefghijklmnop").unwrap();
+
+    fn check<C: BlockEncrypt<BlockSize = U16> + KeyInit>(
+        key: &[u8], alphabet: &Alphabet, tweak: [u8; 8], plain: &str, cipher: &str,
+    ) {
+        let ff3 = Ff3_1::<C>::new_from_slice(key, alphabet.radix()).unwrap();
+        let (tl, tr) = (tweak[..4].try_into().unwrap(), tweak[4..].try_into().unwrap());
+
+        let mut numerals = alphabet.numerals(plain).unwrap();
+        ff3.feistel(tl, tr, &mut numerals, false).unwrap();
+        assert_eq!(alphabet.string(&numerals).unwrap(), cipher);
+        ff3.feistel(tl, tr, &mut numerals, true).unwrap();
+        assert_eq!(alphabet.string(&numerals).unwrap(), plain);
+    }
+
+    let t1 = hex!("d8e7920afa330a73");
+    let t2 = hex!("9a768a92f60e12d8");
+    let t0 = [0; 8];
+    let short = "890121234567890000";
+    let long = "89012123456789000000789000000";
+
+    check::<Aes128>(&key[..16], &digits, t1, short, "750918814058654607");
+    check::<Aes128>(&key[..16], &digits, t2, short, "018989839189395384");
+    check::<Aes128>(&key[..16], &digits, t1, long, "48598367162252569629397416226");
+    check::<Aes128>(&key[..16], &digits, t0, long, "34695224821734535122613701434");
+    check::<Aes128>(&key[..16], &radix26, t2, "0123456789abcdefghi", "g2pk40i992fn20cjakb");
+    check::<Aes192>(&key[..24], &digits, t1, short, "646965393875028755");
+    check::<Aes192>(&key[..24], &digits, t2, short, "961610514491424446");
+    check::<Aes192>(&key[..24], &digits, t1, long, "53048884065350204541786380807");
+    check::<Aes192>(&key[..24], &digits, t0, long, "98083802678820389295041483512");
+    check::<Aes192>(&key[..24], &radix26, t2, "0123456789abcdefghi", "i0ihe2jfj7a9opf9p88");
+    check::<Aes256>(&key, &digits, t1, short, "922011205562777495");
+    check::<Aes256>(&key, &digits, t2, short, "504149865578056140");
+    check::<Aes256>(&key, &digits, t1, long, "04344343235792599165734622699");
+    check::<Aes256>(&key, &digits, t0, long, "30859239999374053872365555822");
+    check::<Aes256>(&key, &radix26, t2, "0123456789abcdefghi", "p0b2godfja9bhb7bk38");
+}
+
+// NIST ACVP FF3-1 samples
+#[test]
+fn test_ff3_1_vectors(){
+    use aes::Aes128;
+
+    let digits = Alphabet::new(DECIMAL).unwrap();
+    let vectors = [
+        (hex!("2de79d232df5585d68ce47882ae256d6"), hex!("cbd09280979564"), "3992520240", "8901801106"),
+        (
+            hex!("01c63017111438f7fc8e24eb16c71ab5"), hex!("c4e822dcd09f27"),
+            "60761757463116869318437658042297305934914824457484538562",
+            "35637144092473838892796702739628394376915177448290847293",
+        ),
+    ];
+    for (key, tweak, plain, cipher) in vectors {
+        let ff3 = Ff3_1::<Aes128>::new_from_slice(&key, 10).unwrap();
+        assert_eq!(digits.encrypt(&ff3, &tweak, plain).unwrap(), cipher);
+        assert_eq!(digits.decrypt(&ff3, &tweak, cipher).unwrap(), plain);
+    }
+}
+
+#[test]
+fn test_tekton(){
+    use crate::imp::b128::Tekton128;
+    use rand::Rng;
+
+    let key: [u8; 16] = rand::thread_rng().gen();
+    let tweak: [u8; 7] = rand::thread_rng().gen();
+
+    for (radix, max) in [(2, 192), (10, 56), (26, 40), (36, 36), (256, 24), (257, 22), (super::MAX_RADIX, 12)] {
+        let ff3 = Ff3_1::<Tekton128>::new_from_slice(&key, radix).unwrap();
+        assert_eq!(ff3.max_len(), max);
+        let min = super::min_len(radix);
+
+        for len in [min, min + 1, (min + max) / 2, max] {
+            let plain: Vec<u16> = (0..len).map(|_| rand::thread_rng().gen_range(0..radix) as u16).collect();
+
+            let mut enc = plain.clone();
+            ff3.encrypt(&tweak, &mut enc).unwrap();
+            assert!(enc.iter().all(|&x| (x as u32) < radix));
+
+            // every bit of the tweak counts, including the nibble split across the halves
+            for bit in [0, 27, 28, 31, 32, 55] {
+                let mut other_tweak = tweak;
+                other_tweak[bit / 8] ^= 0x80 >> (bit % 8);
+                let mut other = plain.clone();
+                ff3.encrypt(&other_tweak, &mut other).unwrap();
+                assert_ne!(other, enc);
+            }
+
+            ff3.decrypt(&tweak, &mut enc).unwrap();
+            assert_eq!(enc, plain);
+        }
+
+        let mut short = vec![0; min - 1];
+        assert_eq!(ff3.encrypt(&tweak, &mut short), Err(Error::InvalidLength));
+        let mut long = vec![0; max + 1];
+        assert_eq!(ff3.encrypt(&tweak, &mut long), Err(Error::InvalidLength));
+    }
+
+    let digits = Alphabet::new(DECIMAL).unwrap();
+    let ff3 = Ff3_1::<Tekton128>::new_from_slice(&key, 10).unwrap();
+    let token = digits.encrypt(&ff3, &tweak, "000123456789").unwrap();
+    assert_eq!(token.len(), 12);
+    assert_eq!(digits.decrypt(&ff3, &tweak, &token).unwrap(), "000123456789");
+    assert_eq!(digits.encrypt(&ff3, &tweak, "0001234x6789"), Err(Error::InvalidNumeral));
+}
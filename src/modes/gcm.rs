@@ -0,0 +1,334 @@
+use aead::{
+    consts::{U0, U12, U16}, AeadCore, AeadInPlace, Key, KeyInit, KeySizeUser, Nonce, Tag,
+};
+use cipher::{BlockEncrypt, StreamCipher};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+use super::ctr::{Ctr, Split};
+use super::{Block, BLOCK_SIZE};
+
+
+/// Longest plaintext GCM allows with a 32-bit block counter, 2^36 - 32 bytes.
+pub const MAX_PLAINTEXT: u64 = (1 << 36) - 32;
+
+/// Longest associated data, 2^61 - 1 bytes.
+pub const MAX_AD: u64 = (1 << 61) - 1;
+
+
+/// GHASH, the universal hash of GCM, over GF(2^128) with the bit order of NIST SP 800-38D.
+#[derive(Clone)]
+struct GHash {
+    h: u128,
+    y: u128,
+}
+
+impl GHash {
+
+    fn new(h: u128) -> Self {
+        GHash { h, y: 0 }
+    }
+
+    /// Hashes `data` in whole blocks, zero padding the last one.
+    fn update_padded(&mut self, data: &[u8]) {
+        for chunk in data.chunks(BLOCK_SIZE) {
+            let mut block = [0; BLOCK_SIZE];
+            block[..chunk.len()].copy_from_slice(chunk);
+            self.y = gf_mul(self.y ^ u128::from_be_bytes(block), self.h);
+        }
+    }
+
+    fn finalize(mut self, ad_len: usize, text_len: usize) -> u128 {
+        let lengths = ((ad_len as u128 * 8) << 64) | (text_len as u128 * 8);
+        self.y = gf_mul(self.y ^ lengths, self.h);
+        self.y
+    }
+}
+
+impl Drop for GHash {
+    fn drop(&mut self) {
+        self.h.zeroize();
+        self.y.zeroize();
+    }
+}
+
+// Shift and add without branches or table lookups on secret bits.
+pub(crate) fn gf_mul(x: u128, y: u128) -> u128 {
+    const R: u128 = 0xe1 << 120;
+    let mut z = 0;
+    let mut v = y;
+    for i in (0..128).rev() {
+        z ^= v & 0u128.wrapping_sub((x >> i) & 1);
+        v = (v >> 1) ^ (R & 0u128.wrapping_sub(v & 1));
+    }
+    z
+}
+
+
+/// GCM with 96-bit nonces and 128-bit tags over any 128-bit block cipher.
+/// Built from a key through `KeyInit`, or from a keyed cipher through `From<C>`.
+#[derive(Clone)]
+pub struct Gcm<C> {
+    cipher: C,
+    h: u128,
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> From<C> for Gcm<C> {
+    fn from(cipher: C) -> Self {
+        let mut h = Block::default();
+        cipher.encrypt_block(&mut h);
+        Gcm { cipher, h: u128::from_be_bytes(h.into()) }
+    }
+}
+
+impl<C: KeySizeUser> KeySizeUser for Gcm<C> {
+    type KeySize = C::KeySize;
+}
+
+impl<C: BlockEncrypt<BlockSize = U16> + KeyInit> KeyInit for Gcm<C> {
+    fn new(key: &Key<Self>) -> Self {
+        C::new(key).into()
+    }
+}
+
+impl<C> AeadCore for Gcm<C> {
+    type NonceSize = U12;
+    type TagSize = U16;
+    type CiphertextOverhead = U0;
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> AeadInPlace for Gcm<C> {
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> aead::Result<Tag<Self>> {
+        check_lengths(associated_data, buffer)?;
+        self.keystream(nonce).apply_keystream(buffer);
+        Ok(self.tag(nonce, associated_data, buffer))
+    }
+
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &Tag<Self>,
+    ) -> aead::Result<()> {
+        check_lengths(associated_data, buffer)?;
+        let expected = self.tag(nonce, associated_data, buffer);
+        if expected.ct_eq(tag).into() {
+            self.keystream(nonce).apply_keystream(buffer);
+            Ok(())
+        } else {
+            Err(aead::Error)
+        }
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> Gcm<C> {
+
+    // keystream starts at counter 2, counter 1 masks the tag
+    fn keystream(&self, nonce: &Nonce<Self>) -> Ctr<&C> {
+        Ctr::new(&self.cipher, Split::Nonce96Counter32, &counter_block(nonce, 2))
+    }
+
+    fn tag(&self, nonce: &Nonce<Self>, associated_data: &[u8], ciphertext: &[u8]) -> Tag<Self> {
+        let mut ghash = GHash::new(self.h);
+        ghash.update_padded(associated_data);
+        ghash.update_padded(ciphertext);
+        let s = ghash.finalize(associated_data.len(), ciphertext.len());
+
+        let mut mask = Block::from(counter_block(nonce, 1));
+        self.cipher.encrypt_block(&mut mask);
+        (u128::from_be_bytes(mask.into()) ^ s).to_be_bytes().into()
+    }
+}
+
+impl<C> Drop for Gcm<C> {
+    fn drop(&mut self) {
+        self.h.zeroize();
+    }
+}
+
+fn counter_block(nonce: &[u8], counter: u32) -> [u8; 16] {
+    let mut block = [0; 16];
+    block[..12].copy_from_slice(nonce);
+    block[12..].copy_from_slice(&counter.to_be_bytes());
+    block
+}
+
+fn check_lengths(associated_data: &[u8], buffer: &[u8]) -> aead::Result<()> {
+    if associated_data.len() as u64 > MAX_AD || buffer.len() as u64 > MAX_PLAINTEXT {
+        return Err(aead::Error);
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+use hex_literal::hex;
+
+#[cfg(test)]
+struct Vector {
+    key: &'static [u8],
+    nonce: &'static [u8],
+    plaintext: &'static [u8],
+    aad: &'static [u8],
+    ciphertext: &'static [u8],
+    tag: &'static [u8],
+}
+
+// NIST CAVS gcmEncryptExtIV128.rsp and gcmEncryptExtIV256.rsp, 96-bit IVs and 128-bit tags
+#[cfg(test)]
+const AES128_VECTORS: &[Vector] = &[
+    Vector {
+        key: &hex!("11754cd72aec309bf52f7687212e8957"),
+        nonce: &hex!("3c819d9a9bed087615030b65"),
+        plaintext: &hex!(""),
+        aad: &hex!(""),
+        ciphertext: &hex!(""),
+        tag: &hex!("250327c674aaf477aef2675748cf6971"),
+    },
+    Vector {
+        key: &hex!("2fb45e5b8f993a2bfebc4b15b533e0b4"),
+        nonce: &hex!("5b05755f984d2b90f94b8027"),
+        plaintext: &hex!(""),
+        aad: &hex!("e85491b2202caf1d7dce03b97e09331c32473941"),
+        ciphertext: &hex!(""),
+        tag: &hex!("c75b7832b2a2d9bd827412b6ef5769db"),
+    },
+    Vector {
+        key: &hex!("7fddb57453c241d03efbed3ac44e371c"),
+        nonce: &hex!("ee283a3fc75575e33efd4887"),
+        plaintext: &hex!("d5de42b461646c255c87bd2962d3b9a2"),
+        aad: &hex!(""),
+        ciphertext: &hex!("2ccda4a5415cb91e135c2a0f78c9b2fd"),
+        tag: &hex!("b36d1df9b9d5e596f83e8b7f52971cb3"),
+    },
+    Vector {
+        key: &hex!("e5b1e7a94e9e1fda0873571eec713429"),
+        nonce: &hex!("5ddde829a81713346af8e5b7"),
+        plaintext: &hex!("850069e5ed768b5dc9ed7ad485"),
+        aad: &hex!("b0ce75da427fba93da6d3455b2b440a877599a6d8d6d2d66ee90b5cf9a33baaa8329a9ffaac290e8e33f2af2548c2a8a181b3d4d9f8fac860cc26b0d26b9cc53bc9f405afa73605ebeb376f2d1d7fcb065bab92f20f295556ade"),
+        ciphertext: &hex!("c211d9079d5562659db01e17d1"),
+        tag: &hex!("884893fb035d3d7237d47c363de62bb3"),
+    },
+    Vector {
+        key: &hex!("48b7f337cdf9252687ecc760bd8ec184"),
+        nonce: &hex!("3e894ebb16ce82a53c3e05b2"),
+        plaintext: &hex!("bb2bac67a4709430c39c2eb9acfabc0d456c80d30aa1734e57997d548a8f0603"),
+        aad: &hex!("7d924cfd37b3d046a96eb5e132042405c8731e06509787bbeb41f258275746495e884d69871f77634c584bb007312234"),
+        ciphertext: &hex!("d263228b8ce051f67e9baf1ce7df97d10cd5f3bc972362055130c7d13c3ab2e7"),
+        tag: &hex!("71446737ca1fa92e6d026d7d2ed1aa9c"),
+    },
+    Vector {
+        key: &hex!("fe47fcce5fc32665d2ae399e4eec72ba"),
+        nonce: &hex!("5adb9609dbaeb58cbd6e7275"),
+        plaintext: &hex!("7c0e88c88899a779228465074797cd4c2e1498d259b54390b85e3eef1c02df60e743f1b840382c4bccaf3bafb4ca8429bea063"),
+        aad: &hex!("88319d6e1d3ffa5f987199166c8a9b56c2aeba5a"),
+        ciphertext: &hex!("98f4826f05a265e6dd2be82db241c0fbbbf9ffb1c173aa83964b7cf5393043736365253ddbc5db8778371495da76d269e5db3e"),
+        tag: &hex!("291ef1982e4defedaa2249f898556b47"),
+    },
+];
+
+#[cfg(test)]
+const AES256_VECTORS: &[Vector] = &[
+    Vector {
+        key: &hex!("92e11dcdaa866f5ce790fd24501f92509aacf4cb8b1339d50c9c1240935dd08b"),
+        nonce: &hex!("ac93a1a6145299bde902f21a"),
+        plaintext: &hex!("2d71bcfa914e4ac045b2aa60955fad24"),
+        aad: &hex!("1e0889016f67601c8ebea4943bc23ad6"),
+        ciphertext: &hex!("8995ae2e6df3dbf96fac7b7137bae67f"),
+        tag: &hex!("eca5aa77d51d4a0a14d9c51e1da474ab"),
+    },
+    Vector {
+        key: &hex!("69b458f2644af9020463b40ee503cdf083d693815e2659051ae0d039e606a970"),
+        nonce: &hex!("8d1da8ab5f91ccd09205944b"),
+        plaintext: &hex!("f3e0e09224256bf21a83a5de8d"),
+        aad: &hex!("036ad5e5494ef817a8af2f5828784a4bfedd1653"),
+        ciphertext: &hex!("c0a62d77e6031bfdc6b13ae217"),
+        tag: &hex!("a794a9aaee48cd92e47761bf1baff0af"),
+    },
+    Vector {
+        key: &hex!("148579a3cbca86d5520d66c0ec71ca5f7e41ba78e56dc6eebd566fed547fe691"),
+        nonce: &hex!("b08a5ea1927499c6ecbfd4e0"),
+        plaintext: &hex!("9d0b15fdf1bd595f91f8b3abc0f7dec927dfd4799935a1795d9ce00c9b879434420fe42c275a7cd7b39d638fb81ca52b49dc41"),
+        aad: &hex!("e4f963f015ffbb99ee3349bbaf7e8e8e6c2a71c230a48f9d59860a29091d2747e01a5ca572347e247d25f56ba7ae8e05cde2be3c97931292c02370208ecd097ef692687fecf2f419d3200162a6480a57dad408a0dfeb492e2c5d"),
+        ciphertext: &hex!("2097e372950a5e9383c675e89eea1c314f999159f5611344b298cda45e62843716f215f82ee663919c64002a5c198d7878fd3f"),
+        tag: &hex!("adbecdb0d5c2224d804d2886ff9a5760"),
+    },
+];
+
+#[cfg(test)]
+fn check_vectors<C: BlockEncrypt<BlockSize = U16> + KeyInit>(vectors: &[Vector]) {
+    for v in vectors {
+        let gcm = Gcm::<C>::new_from_slice(v.key).unwrap();
+        let nonce = Nonce::<Gcm<C>>::from_slice(v.nonce);
+
+        let mut buffer = v.plaintext.to_vec();
+        let tag = gcm.encrypt_in_place_detached(nonce, v.aad, &mut buffer).unwrap();
+        assert_eq!(buffer, v.ciphertext);
+        assert_eq!(tag.as_slice(), v.tag);
+
+        gcm.decrypt_in_place_detached(nonce, v.aad, &mut buffer, &tag).unwrap();
+        assert_eq!(buffer, v.plaintext);
+
+        #[cfg(feature = "alloc")]
+        {
+            use aead::{Aead, Payload};
+
+            let sealed = gcm.encrypt(nonce, Payload { msg: v.plaintext, aad: v.aad }).unwrap();
+            assert_eq!(sealed, [v.ciphertext, v.tag].concat());
+            let opened = gcm.decrypt(nonce, Payload { msg: &sealed, aad: v.aad }).unwrap();
+            assert_eq!(opened, v.plaintext);
+        }
+    }
+}
+
+#[test]
+fn test_aes_vectors(){
+    check_vectors::<aes::Aes128>(AES128_VECTORS);
+    check_vectors::<aes::Aes256>(AES256_VECTORS);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_tamper(){
+    use crate::imp::{b128::Tekton128, b256::Tekton256};
+    use aead::{Aead, Payload};
+    use rand::Rng;
+
+    fn tamper<C: BlockEncrypt<BlockSize = U16> + KeyInit>(gcm: Gcm<C>) {
+        let nonce = Nonce::<Gcm<C>>::from(rand::thread_rng().gen::<[u8; 12]>());
+        let msg: Vec<u8> = (0..100).map(|_| rand::thread_rng().gen()).collect();
+        let aad = b"header";
+
+        let sealed = gcm.encrypt(&nonce, Payload { msg: &msg, aad }).unwrap();
+        assert_eq!(sealed.len(), msg.len() + 16);
+        assert_eq!(gcm.decrypt(&nonce, Payload { msg: &sealed, aad }).unwrap(), msg);
+
+        for i in 0..sealed.len() {
+            let mut bad = sealed.clone();
+            bad[i] ^= 1 << (i % 8);
+            assert!(gcm.decrypt(&nonce, Payload { msg: &bad, aad }).is_err());
+        }
+        assert!(gcm.decrypt(&nonce, Payload { msg: &sealed, aad: b"Header" }).is_err());
+        assert!(gcm.decrypt(&nonce, Payload { msg: &sealed[..sealed.len() - 1], aad }).is_err());
+
+        let mut other = nonce;
+        other[0] ^= 1;
+        assert!(gcm.decrypt(&other, Payload { msg: &sealed, aad }).is_err());
+
+        // a failed check leaves the buffer as it was
+        let mut buffer = sealed[..msg.len()].to_vec();
+        let mut tag = *Tag::<Gcm<C>>::from_slice(&sealed[msg.len()..]);
+        tag[15] ^= 1;
+        assert!(gcm.decrypt_in_place_detached(&nonce, aad, &mut buffer, &tag).is_err());
+        assert_eq!(buffer, sealed[..msg.len()]);
+    }
+
+    tamper(Gcm::<Tekton128>::new(&rand::thread_rng().gen::<[u8; 16]>().into()));
+    tamper(Gcm::<Tekton256>::new(&rand::thread_rng().gen::<[u8; 32]>().into()));
+}
@@ -0,0 +1,393 @@
+use cipher::{consts::U16, BlockDecrypt, BlockEncrypt, Key, KeyInit, KeySizeUser};
+use zeroize::Zeroize;
+
+use super::gcm::gf_mul;
+use super::{xor, Block, Error, BATCH, BLOCK_SIZE};
+
+
+/// HCTR2 (Crowley, Huckleberry and Biggers), a length-preserving wide-block
+/// mode. Data of one block or longer is enciphered in place under a tweak of
+/// any length and every output bit depends on every input bit, so equal
+/// inputs are only visible as equal outputs under the same tweak. A keyed
+/// cipher can be passed in through `From<C>`.
+#[derive(Clone)]
+pub struct Hctr2<C> {
+    cipher: C,
+    // POLYVAL key E(0) and the XCTR offset E(1), both little-endian
+    h: [u8; 16],
+    l: [u8; 16],
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> From<C> for Hctr2<C> {
+    fn from(cipher: C) -> Self {
+        let mut h = Block::default();
+        cipher.encrypt_block(&mut h);
+        let mut l = Block::from(1u128.to_le_bytes());
+        cipher.encrypt_block(&mut l);
+        Hctr2 { cipher, h: h.into(), l: l.into() }
+    }
+}
+
+impl<C: KeySizeUser> KeySizeUser for Hctr2<C> {
+    type KeySize = C::KeySize;
+}
+
+impl<C: BlockEncrypt<BlockSize = U16> + KeyInit> KeyInit for Hctr2<C> {
+    fn new(key: &Key<Self>) -> Self {
+        C::new(key).into()
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> Hctr2<C> {
+
+    /// Encrypts `data` in place, it must be at least one block long.
+    pub fn encrypt(&self, tweak: &[u8], data: &mut [u8]) -> Result<(), Error> {
+        if data.len() < BLOCK_SIZE {
+            return Err(Error::InvalidLength);
+        }
+        let (m, n) = data.split_at_mut(BLOCK_SIZE);
+        let hash = self.tweak_hash(tweak, n.len());
+
+        let mut mm = Block::clone_from_slice(m);
+        xor(&mut mm, &hash.clone().finalize(n));
+        let mut uu = mm;
+        self.cipher.encrypt_block(&mut uu);
+
+        self.xctr(&mm, &uu, n);
+        m.copy_from_slice(&uu);
+        xor(m, &hash.finalize(n));
+        mm.as_mut_slice().zeroize();
+        Ok(())
+    }
+
+    /// Hash state after the tweak, the first block also encodes whether the
+    /// message is a whole number of blocks.
+    fn tweak_hash(&self, tweak: &[u8], len: usize) -> Polyval {
+        let mut hash = Polyval::new(&self.h);
+        let padded = !len.is_multiple_of(BLOCK_SIZE) as u128;
+        hash.update_padded(&(tweak.len() as u128 * 16 + 2 + padded).to_le_bytes());
+        hash.update_padded(tweak);
+        hash
+    }
+
+    /// XCTR keyed by `mm ^ uu ^ L`, block i of the keystream is E(S ^ i) from i = 1.
+    fn xctr(&self, mm: &Block, uu: &Block, data: &mut [u8]) {
+        let s = u128::from_le_bytes((*mm).into()) ^ u128::from_le_bytes((*uu).into())
+            ^ u128::from_le_bytes(self.l);
+
+        let mut i = 1u128;
+        for chunk in data.chunks_mut(BATCH * BLOCK_SIZE) {
+            let n = chunk.len().div_ceil(BLOCK_SIZE);
+            let mut keystream = [Block::default(); BATCH];
+            for ks in &mut keystream[..n] {
+                *ks = (s ^ i).to_le_bytes().into();
+                i += 1;
+            }
+            self.cipher.encrypt_blocks(&mut keystream[..n]);
+            for (part, ks) in chunk.chunks_mut(BLOCK_SIZE).zip(&mut keystream[..n]) {
+                xor(part, ks);
+                ks.as_mut_slice().zeroize();
+            }
+        }
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16> + BlockDecrypt> Hctr2<C> {
+
+    pub fn decrypt(&self, tweak: &[u8], data: &mut [u8]) -> Result<(), Error> {
+        if data.len() < BLOCK_SIZE {
+            return Err(Error::InvalidLength);
+        }
+        let (u, v) = data.split_at_mut(BLOCK_SIZE);
+        let hash = self.tweak_hash(tweak, v.len());
+
+        let mut uu = Block::clone_from_slice(u);
+        xor(&mut uu, &hash.clone().finalize(v));
+        let mut mm = uu;
+        self.cipher.decrypt_block(&mut mm);
+
+        self.xctr(&mm, &uu, v);
+        u.copy_from_slice(&mm);
+        xor(u, &hash.finalize(v));
+        mm.as_mut_slice().zeroize();
+        Ok(())
+    }
+}
+
+impl<C> Drop for Hctr2<C> {
+    fn drop(&mut self) {
+        self.h.zeroize();
+        self.l.zeroize();
+    }
+}
+
+
+/// POLYVAL (RFC 8452) through the GHASH multiplication: the byte-reversed
+/// POLYVAL of a message is the GHASH of the byte-reversed blocks under x * H.
+#[derive(Clone)]
+struct Polyval {
+    h: u128,
+    y: u128,
+}
+
+impl Polyval {
+
+    fn new(h: &[u8; 16]) -> Self {
+        const R: u128 = 0xe1 << 120;
+        let h = u128::from_le_bytes(*h);
+        let h = (h >> 1) ^ (R & 0u128.wrapping_sub(h & 1));
+        Polyval { h, y: 0 }
+    }
+
+    /// Hashes `data` in whole blocks, zero padding the last one.
+    fn update_padded(&mut self, data: &[u8]) {
+        for chunk in data.chunks(BLOCK_SIZE) {
+            let mut block = [0; BLOCK_SIZE];
+            block[..chunk.len()].copy_from_slice(chunk);
+            self.y = gf_mul(self.y ^ u128::from_le_bytes(block), self.h);
+        }
+    }
+
+    /// Hashes the message part, a partial last block is padded with 1 and zeros.
+    fn finalize(mut self, data: &[u8]) -> [u8; 16] {
+        let (full, rest) = data.split_at(data.len() - data.len() % BLOCK_SIZE);
+        self.update_padded(full);
+        if !rest.is_empty() {
+            let mut block = [0; BLOCK_SIZE];
+            block[..rest.len()].copy_from_slice(rest);
+            block[rest.len()] = 1;
+            self.update_padded(&block);
+            block.zeroize();
+        }
+        self.y.to_le_bytes()
+    }
+}
+
+impl Drop for Polyval {
+    fn drop(&mut self) {
+        self.h.zeroize();
+        self.y.zeroize();
+    }
+}
+
+
+#[cfg(test)]
+use hex_literal::hex;
+
+#[cfg(test)]
+struct Vector {
+    key: &'static [u8],
+    tweak: &'static [u8],
+    plaintext: &'static [u8],
+    ciphertext: &'static [u8],
+}
+
+// github.com/google/hctr2 test_vectors/ours/HCTR2, a sample of the lengths and tweak sizes
+#[cfg(test)]
+const AES128_VECTORS: &[Vector] = &[
+    Vector {
+        key: &hex!("74f98f60786abfa85b0bbba059e0f91e"),
+        tweak: &hex!(""),
+        plaintext: &hex!("6b26837bdc1c583dc142c6ab7b3f43b0"),
+        ciphertext: &hex!("dd05a8ae51f1e8212fd6c33b9467036d"),
+    },
+    Vector {
+        key: &hex!("a7ac03fd6743e876440b75552e11f495"),
+        tweak: &hex!(""),
+        plaintext: &hex!("ec44c7a2248463e3df9bdb72825aa70336658f0f3ce91df874b80d3dde3b70"),
+        ciphertext: &hex!("ea747e8cc77b43b53e80170eb72285bb5833aaaac3b29dfcb6f80528faa6ef"),
+    },
+    Vector {
+        key: &hex!("d09431cdb9d16d736d58d5b4e86146e2"),
+        tweak: &hex!("6f"),
+        plaintext: &hex!("5d4dc932966d0e3a74537ad3ac662771"),
+        ciphertext: &hex!("3a3416b01ddd84d44e2fdf636e7dc782"),
+    },
+    Vector {
+        key: &hex!("92a952ef424b4b6922bd2761c1e9765b"),
+        tweak: &hex!("26"),
+        plaintext: &hex!("c5e240835494265beb2800fa7876077f2c6a0cc8f35da42bd075593c1e535a"),
+        ciphertext: &hex!("fcc7ed9b6102b8d638190ca9e1d6197d563b84fab39e24eca79483c511f09a"),
+    },
+    Vector {
+        key: &hex!("02a54b93af12c47ba3a73fa6718cfadb"),
+        tweak: &hex!("8c91c390926cacb8f452f8464bddc27d"),
+        plaintext: &hex!("76e2979dde56a4d63f05aa4e9a6a91fcd740c2a1734406d4fad98815da9108"),
+        ciphertext: &hex!("caa0acb5e5ea136e2b5461a3dc1f67c7589140a41c21d12abbf023986858ff"),
+    },
+    Vector {
+        key: &hex!("8c9b9e4f92d497ddec1efa87ada007c7"),
+        tweak: &hex!("fee684182f9436ac555000bf6d59c907"),
+        plaintext: &hex!(
+            "a02a2d6fb1ababa3a3e54697c9768572a4d232cbf2ea3af7248e690256692715"
+            "494db72f28b84acf1880914c9970663031a11db526ee2f68184ef6c02a2c04c5"
+            "8c28f7f583488e5cb0dc44e40e7970c8c53829da758be5521aeae8936803fa9b"
+            "2659e25c5bb7735bca13fbc16dd65d25f09e10101cfb0b9ceafe807c16a8a77a"
+        ),
+        ciphertext: &hex!(
+            "f9241a4261529f33667574451d9339fc82fee42ad71f5397524abaf489c98814"
+            "eb1dfe4b42f85a62dad159a8e65ded6fc917ee9b2756dbae33a2e686434ba3e1"
+            "752349bced21370f188712d85effb5daa129ae5e9ee93bbed24e8417c03af708"
+            "516a82077a58c4392dc816e41974d95b85a0ad63141504e2424614e467a0d3a4"
+        ),
+    },
+    Vector {
+        key: &hex!("7a6a153962565130601c16d209aff90e"),
+        tweak: &hex!("d7d29ba5f608deac4fa98f546898bb45531fe1c951765ef455b232af69edee08"),
+        plaintext: &hex!("9d1b5daba3a361c1d875e4f8f7f0742155080d572f229f6832f1e174b4ed1a"),
+        ciphertext: &hex!("4fad8fed97198014bcab969b27cd38573228fd2a43d7da25c62438e732e771"),
+    },
+    Vector {
+        key: &hex!("1cb6fb7d1f09eeeed20a686ef792540a"),
+        tweak: &hex!(
+            "e5b82bef284f573cc0848f68de5f7a063324c755ed70ab5e526712e012561b24"
+            "8103dc8d1e7ac22d750f062d9bc719"
+        ),
+        plaintext: &hex!("c44f6a1154b2d79ab08ef49b96434298fabd089ec10d48ff74351209dae58f"),
+        ciphertext: &hex!("72a2fcc38292a4e72ed39e795fee79aa3f84214c397c36bf822d11f3d8226b"),
+    },
+];
+
+#[cfg(test)]
+const AES256_VECTORS: &[Vector] = &[
+    Vector {
+        key: &hex!("ab4abbee329591540b0e90ea97c4866928fbab1ccf39ff387a4377629327aa05"),
+        tweak: &hex!(""),
+        plaintext: &hex!("16b173cadbab4790ad98ab76b22b059e19"),
+        ciphertext: &hex!("18e088ef27b530ea44cd2e22d2ca28e875"),
+    },
+    Vector {
+        key: &hex!("09a1100db2b3e90e9ca0930f6a94421c7ed90f5cafc9647193086d4efaae0090"),
+        tweak: &hex!(""),
+        plaintext: &hex!(
+            "9627bcb5d3beb4b2a0e122cf69ac194aa61935c78b036d737a923b517a803c1c"
+            "1a8dafc7e4e663c73af987102558ca5d"
+        ),
+        ciphertext: &hex!(
+            "0a9aececc5e248368096199e129fb4606c98d23bc6b23663d09ce210ffe8fff5"
+            "679e4b2e081f84f8b82478bc90909668"
+        ),
+    },
+    Vector {
+        key: &hex!("e191946322c155b9aac95ea67de485363dfb8f880b6e7dbde289054f4246f798"),
+        tweak: &hex!("1d"),
+        plaintext: &hex!("70168e0db3f484bbb48499b4f42f5a86da"),
+        ciphertext: &hex!("df3f6c024dd57ff35c8ce4f048797bf407"),
+    },
+    Vector {
+        key: &hex!("6717ddf8c20bd5cf75d6661bf01c8f50eec17185d9ba243f9e49d53e736f7334"),
+        tweak: &hex!("b754af638b34003608346e8cb35ead56"),
+        plaintext: &hex!(
+            "369968139ce3a8e4d3fc0221b3831a507ecd567b343073a5de43a1b689aa9cb9"
+            "248da7443942dcdfcc7c15ed786e67a1"
+        ),
+        ciphertext: &hex!(
+            "45e2f74cdcaba92ccf6c23716d607b06f94cb32968ac6cdbe28310bf7b45a0b8"
+            "2704dc3ab1c1afe660c3fb7cdbb4764e"
+        ),
+    },
+    Vector {
+        key: &hex!("6c5c47f684a1aae9a332c4debae1cae3ed0d407178dc8a8b2baf6928c19ee298"),
+        tweak: &hex!("8fa9b01643d2b3a1d12b710db0574b65cc2c934f8e1b5ef7efb79428f197f78f"),
+        plaintext: &hex!(
+            "cb7588831a1dab75e5a9b56d02f729b49c1032d9a1e50798f21ca59863346e84"
+            "15915cc9cf885fdeff876e79f87a3cc0"
+        ),
+        ciphertext: &hex!(
+            "e07c158e26f31b9488d5c1e0d5f3fb88c3641b76a82c7ae781a5c2c79f40352c"
+            "742013322157ec746d7b576c23a6f274"
+        ),
+    },
+    Vector {
+        key: &hex!("d52d3ffa65eb06f9a7f91ad752c1c79d4aa0ac205943035056f8c2e933999b84"),
+        tweak: &hex!(
+            "203008c65f18d8080426acb0db3ea0ee14e43dc1ab9d5c966d82b2ecbd49e4bc"
+            "4cacc0800ef781452ad9d6be6ad3a0"
+        ),
+        plaintext: &hex!("9986657d9a4f84377d726586b4005f58e8"),
+        ciphertext: &hex!("e4c06b6d834d7e7f7dd8274c1d530016b2"),
+    },
+    Vector {
+        key: &hex!("e951573ad286fe8358af8af8d09e8acae6f6d06157881bc1618906de580a9b94"),
+        tweak: &hex!(
+            "b27f983a20e2e4af54d62ed714f6c7ee5fd0b230bdef3a5d41f9d26a6b28633e"
+            "6842fcaf66d939ba6ee3bb445e5f20"
+        ),
+        plaintext: &hex!(
+            "1b119ff6da3c42c340f7592afbc53c2d95cb88082350d7eacb2e6ac0b24e2790"
+            "936c54abdc8b920e0e55533a5de6b98e80c3c856eb5673486e2df8281ead5303"
+            "6fb14fb784b4d649eb27f28e79efdee5bb848344239d0fa6d4ed85b1529a0c5c"
+            "83c1bebbbae2914089b0a1e9dd7d5545a58ca8db5cc244419cdd2fb94f4282a2"
+        ),
+        ciphertext: &hex!(
+            "80754362e4a7240eeef35a859e9ee8988c3657f21bf65727ad48a0af87096e86"
+            "5a97e3da09fdbf7eb3c3adfdbb71e1c2aa1fc4eafa26b19e49e94228af0b9d5a"
+            "974b84bdb52c31cb9b71859eb1d994c532c2b3fab54044403c30b147b7beae8f"
+            "cd96574cf6f576aa4eb34082e41cd525c2c279cd5ef9120f7c2ec8637c8f39db"
+        ),
+    },
+];
+
+#[cfg(test)]
+fn check_vectors<C: BlockEncrypt<BlockSize = U16> + BlockDecrypt + KeyInit>(vectors: &[Vector]) {
+    for v in vectors {
+        let hctr2 = Hctr2::<C>::new_from_slice(v.key).unwrap();
+
+        let mut buffer = v.plaintext.to_vec();
+        hctr2.encrypt(v.tweak, &mut buffer).unwrap();
+        assert_eq!(buffer, v.ciphertext);
+
+        hctr2.decrypt(v.tweak, &mut buffer).unwrap();
+        assert_eq!(buffer, v.plaintext);
+    }
+}
+
+#[test]
+fn test_aes_vectors(){
+    check_vectors::<aes::Aes128>(AES128_VECTORS);
+    check_vectors::<aes::Aes256>(AES256_VECTORS);
+}
+
+#[test]
+fn test_tekton(){
+    use crate::imp::b128::Tekton128;
+    use rand::Rng;
+
+    let hctr2 = Hctr2::<Tekton128>::new(&rand::thread_rng().gen::<[u8; 16]>().into());
+    let mut data = [0u8; 100];
+    rand::thread_rng().fill(&mut data[..]);
+    let tweak: [u8; 20] = rand::thread_rng().gen();
+
+    assert_eq!(hctr2.encrypt(&tweak, &mut data[..15]), Err(Error::InvalidLength));
+    assert_eq!(hctr2.decrypt(&tweak, &mut []), Err(Error::InvalidLength));
+
+    for len in [16, 17, 31, 32, 33, 64, 100] {
+        for tweak in [&tweak[..0], &tweak[..1], &tweak[..16], &tweak[..]] {
+            let plain = &data[..len];
+            let mut enc = [0u8; 100];
+            enc[..len].copy_from_slice(plain);
+            hctr2.encrypt(tweak, &mut enc[..len]).unwrap();
+            assert_ne!(&enc[..len], plain);
+
+            // flipping any bit of the input or the tweak changes every whole block of the output
+            let changed = |other: &[u8]| other.chunks_exact(BLOCK_SIZE).zip(enc[..len].chunks_exact(BLOCK_SIZE))
+                .all(|(a, b)| a != b);
+            for bit in [0, 7, 127, 128, len * 8 - 1].into_iter().filter(|&bit| bit < len * 8) {
+                let mut other = [0u8; 100];
+                other[..len].copy_from_slice(plain);
+                other[bit / 8] ^= 1 << (bit % 8);
+                hctr2.encrypt(tweak, &mut other[..len]).unwrap();
+                assert!(changed(&other[..len]));
+            }
+            let mut other = [0u8; 100];
+            other[..len].copy_from_slice(plain);
+            let mut other_tweak = [0u8; 21];
+            other_tweak[..tweak.len()].copy_from_slice(tweak);
+            hctr2.encrypt(&other_tweak[..tweak.len() + 1], &mut other[..len]).unwrap();
+            assert!(changed(&other[..len]));
+
+            hctr2.decrypt(tweak, &mut enc[..len]).unwrap();
+            assert_eq!(&enc[..len], plain);
+        }
+    }
+}
@@ -0,0 +1,389 @@
+#[cfg(feature = "alloc")]
+use alloc::{vec, vec::Vec};
+
+use cipher::{consts::U16, BlockDecrypt, BlockEncrypt};
+use subtle::{ConstantTimeEq, ConstantTimeGreater};
+use zeroize::{Zeroize, Zeroizing};
+
+use super::{Block, Error};
+
+
+/// Initial value of RFC 3394, checked on unwrap.
+pub const IV: u64 = 0xa6a6_a6a6_a6a6_a6a6;
+
+/// High half of the alternative initial value of RFC 5649, the low half is the key length.
+pub const AIV: u32 = 0xa659_59a6;
+
+/// Semiblock, half a cipher block.
+const SEMIBLOCK: usize = 8;
+
+
+/// Length of `len` bytes of key material wrapped with `wrap`.
+pub fn wrapped_len(len: usize) -> usize {
+    len + SEMIBLOCK
+}
+
+/// Length of `len` bytes of key material wrapped with `wrap_padded`.
+pub fn padded_wrapped_len(len: usize) -> usize {
+    len.div_ceil(SEMIBLOCK) * SEMIBLOCK + SEMIBLOCK
+}
+
+/// Key wrap (RFC 3394) of `key`, a multiple of 8 bytes and at least 16, under
+/// the key-encryption key `kek`. `out` needs room for `wrapped_len(key.len())`
+/// bytes, the wrapped key is returned.
+pub fn wrap<'a, C>(kek: &C, key: &[u8], out: &'a mut [u8]) -> Result<&'a [u8], Error>
+where
+    C: BlockEncrypt<BlockSize = U16>,
+{
+    if key.len() < 2 * SEMIBLOCK || !key.len().is_multiple_of(SEMIBLOCK) {
+        return Err(Error::InvalidLength);
+    }
+    let out = out.get_mut(..wrapped_len(key.len())).ok_or(Error::InvalidLength)?;
+    let (a, r) = out.split_at_mut(SEMIBLOCK);
+    r.copy_from_slice(key);
+    a.copy_from_slice(&w(kek, IV, r).to_be_bytes());
+    Ok(out)
+}
+
+/// Reverses `wrap` into `out`, which needs room for `wrapped.len() - 8` bytes.
+/// A failed integrity check leaves `out` zeroed.
+pub fn unwrap<'a, C>(kek: &C, wrapped: &[u8], out: &'a mut [u8]) -> Result<&'a [u8], Error>
+where
+    C: BlockDecrypt<BlockSize = U16>,
+{
+    if wrapped.len() < 3 * SEMIBLOCK || !wrapped.len().is_multiple_of(SEMIBLOCK) {
+        return Err(Error::InvalidLength);
+    }
+    let out = out.get_mut(..wrapped.len() - SEMIBLOCK).ok_or(Error::InvalidLength)?;
+    let (a, c) = wrapped.split_at(SEMIBLOCK);
+    out.copy_from_slice(c);
+    let a = w_inv(kek, u64::from_be_bytes(a.try_into().unwrap()), out);
+
+    if a.ct_eq(&IV).into() {
+        Ok(out)
+    } else {
+        out.zeroize();
+        Err(Error::IntegrityCheck)
+    }
+}
+
+/// Key wrap with padding (RFC 5649) of 1 to 2^32 - 1 bytes of key material.
+/// `out` needs room for `padded_wrapped_len(key.len())` bytes.
+pub fn wrap_padded<'a, C>(kek: &C, key: &[u8], out: &'a mut [u8]) -> Result<&'a [u8], Error>
+where
+    C: BlockEncrypt<BlockSize = U16>,
+{
+    if key.is_empty() || key.len() > u32::MAX as usize {
+        return Err(Error::InvalidLength);
+    }
+    let out = out.get_mut(..padded_wrapped_len(key.len())).ok_or(Error::InvalidLength)?;
+    let aiv = (AIV as u64) << 32 | key.len() as u64;
+    out[..SEMIBLOCK].copy_from_slice(&aiv.to_be_bytes());
+    out[SEMIBLOCK..SEMIBLOCK + key.len()].copy_from_slice(key);
+    out[SEMIBLOCK + key.len()..].fill(0);
+
+    // a single semiblock is enciphered once, with the AIV as its first half
+    if out.len() == 2 * SEMIBLOCK {
+        let block = Block::from_mut_slice(out);
+        kek.encrypt_block(block);
+    } else {
+        let (a, r) = out.split_at_mut(SEMIBLOCK);
+        a.copy_from_slice(&w(kek, aiv, r).to_be_bytes());
+    }
+    Ok(out)
+}
+
+/// Reverses `wrap_padded` into `out`, which needs room for `wrapped.len() - 8`
+/// bytes, and returns the key without padding. The AIV, the length and the
+/// padding are all checked before failing, a failed check leaves `out` zeroed.
+pub fn unwrap_padded<'a, C>(kek: &C, wrapped: &[u8], out: &'a mut [u8]) -> Result<&'a [u8], Error>
+where
+    C: BlockDecrypt<BlockSize = U16>,
+{
+    if wrapped.len() < 2 * SEMIBLOCK || !wrapped.len().is_multiple_of(SEMIBLOCK) {
+        return Err(Error::InvalidLength);
+    }
+    let out = out.get_mut(..wrapped.len() - SEMIBLOCK).ok_or(Error::InvalidLength)?;
+
+    let a = if wrapped.len() == 2 * SEMIBLOCK {
+        let mut block = Block::clone_from_slice(wrapped);
+        kek.decrypt_block(&mut block);
+        out.copy_from_slice(&block[SEMIBLOCK..]);
+        let a = u64::from_be_bytes(block[..SEMIBLOCK].try_into().unwrap());
+        block.as_mut_slice().zeroize();
+        a
+    } else {
+        let (a, c) = wrapped.split_at(SEMIBLOCK);
+        out.copy_from_slice(c);
+        w_inv(kek, u64::from_be_bytes(a.try_into().unwrap()), out)
+    };
+
+    // the length must fall in the last semiblock and the rest of it be zero
+    let len = a & u32::MAX as u64;
+    let padded = out.len() as u64;
+    let mut valid = ((a >> 32) as u32).ct_eq(&AIV)
+        & len.ct_gt(&(padded - SEMIBLOCK as u64))
+        & !len.ct_gt(&padded);
+    for (i, b) in out.iter().enumerate().skip(out.len() - SEMIBLOCK) {
+        valid &= b.ct_eq(&0) | len.ct_gt(&(i as u64));
+    }
+
+    if valid.into() {
+        Ok(&out[..len as usize])
+    } else {
+        out.zeroize();
+        Err(Error::IntegrityCheck)
+    }
+}
+
+#[cfg(feature = "alloc")]
+pub fn wrap_vec<C>(kek: &C, key: &[u8]) -> Result<Vec<u8>, Error>
+where
+    C: BlockEncrypt<BlockSize = U16>,
+{
+    let mut out = vec![0; wrapped_len(key.len())];
+    wrap(kek, key, &mut out)?;
+    Ok(out)
+}
+
+#[cfg(feature = "alloc")]
+pub fn unwrap_vec<C>(kek: &C, wrapped: &[u8]) -> Result<Vec<u8>, Error>
+where
+    C: BlockDecrypt<BlockSize = U16>,
+{
+    let mut out = vec![0; wrapped.len().saturating_sub(SEMIBLOCK)];
+    unwrap(kek, wrapped, &mut out)?;
+    Ok(out)
+}
+
+#[cfg(feature = "alloc")]
+pub fn wrap_padded_vec<C>(kek: &C, key: &[u8]) -> Result<Vec<u8>, Error>
+where
+    C: BlockEncrypt<BlockSize = U16>,
+{
+    let mut out = vec![0; padded_wrapped_len(key.len())];
+    wrap_padded(kek, key, &mut out)?;
+    Ok(out)
+}
+
+#[cfg(feature = "alloc")]
+pub fn unwrap_padded_vec<C>(kek: &C, wrapped: &[u8]) -> Result<Vec<u8>, Error>
+where
+    C: BlockDecrypt<BlockSize = U16>,
+{
+    let mut out = vec![0; wrapped.len().saturating_sub(SEMIBLOCK)];
+    let len = unwrap_padded(kek, wrapped, &mut out)?.len();
+    out.truncate(len);
+    Ok(out)
+}
+
+/// Wraps the key of a `Tekton128`, as passed to `Tekton128::new` or to
+/// `KeyInit::new`, which picks `KeySchedule::V2`.
+pub fn wrap_tekton128_key<C>(kek: &C, key: &[u8; 16]) -> [u8; 24]
+where
+    C: BlockEncrypt<BlockSize = U16>,
+{
+    let mut out = [0; 24];
+    wrap(kek, key, &mut out).expect("whole semiblocks");
+    out
+}
+
+pub fn unwrap_tekton128_key<C>(kek: &C, wrapped: &[u8; 24]) -> Result<Zeroizing<[u8; 16]>, Error>
+where
+    C: BlockDecrypt<BlockSize = U16>,
+{
+    let mut key = Zeroizing::new([0; 16]);
+    unwrap(kek, wrapped, key.as_mut())?;
+    Ok(key)
+}
+
+/// Wraps the key of a `Tekton256`, as passed to `Tekton256::new` or to
+/// `KeyInit::new`, which picks `KeySchedule::V2`.
+pub fn wrap_tekton256_key<C>(kek: &C, key: &[u8; 32]) -> [u8; 40]
+where
+    C: BlockEncrypt<BlockSize = U16>,
+{
+    let mut out = [0; 40];
+    wrap(kek, key, &mut out).expect("whole semiblocks");
+    out
+}
+
+pub fn unwrap_tekton256_key<C>(kek: &C, wrapped: &[u8; 40]) -> Result<Zeroizing<[u8; 32]>, Error>
+where
+    C: BlockDecrypt<BlockSize = U16>,
+{
+    let mut key = Zeroizing::new([0; 32]);
+    unwrap(kek, wrapped, key.as_mut())?;
+    Ok(key)
+}
+
+/// Wrapping function W of RFC 3394 over the semiblocks `r`, returns the final A.
+fn w<C: BlockEncrypt<BlockSize = U16>>(kek: &C, mut a: u64, r: &mut [u8]) -> u64 {
+    let n = r.len() / SEMIBLOCK;
+    let mut block = Block::default();
+    for j in 0..6 {
+        for (i, r) in r.chunks_exact_mut(SEMIBLOCK).enumerate() {
+            block[..SEMIBLOCK].copy_from_slice(&a.to_be_bytes());
+            block[SEMIBLOCK..].copy_from_slice(r);
+            kek.encrypt_block(&mut block);
+            a = u64::from_be_bytes(block[..SEMIBLOCK].try_into().unwrap()) ^ (n * j + i + 1) as u64;
+            r.copy_from_slice(&block[SEMIBLOCK..]);
+        }
+    }
+    block.as_mut_slice().zeroize();
+    a
+}
+
+/// Unwrapping function W^-1, returns the recovered A.
+fn w_inv<C: BlockDecrypt<BlockSize = U16>>(kek: &C, mut a: u64, r: &mut [u8]) -> u64 {
+    let n = r.len() / SEMIBLOCK;
+    let mut block = Block::default();
+    for j in (0..6).rev() {
+        for (i, r) in r.chunks_exact_mut(SEMIBLOCK).enumerate().rev() {
+            block[..SEMIBLOCK].copy_from_slice(&(a ^ (n * j + i + 1) as u64).to_be_bytes());
+            block[SEMIBLOCK..].copy_from_slice(r);
+            kek.decrypt_block(&mut block);
+            a = u64::from_be_bytes(block[..SEMIBLOCK].try_into().unwrap());
+            r.copy_from_slice(&block[SEMIBLOCK..]);
+        }
+    }
+    block.as_mut_slice().zeroize();
+    a
+}
+
+
+#[cfg(test)]
+use hex_literal::hex;
+
+// RFC 3394, section 4
+#[test]
+fn test_rfc3394_vectors(){
+    use aes::{Aes128, Aes192, Aes256};
+    use cipher::KeyInit;
+
+    let kek: [u8; 32] = core::array::from_fn(|i| i as u8);
+    let key = hex!("00112233445566778899aabbccddeeff 000102030405060708090a0b0c0d0e0f");
+
+    fn check<C: BlockEncrypt<BlockSize = U16> + BlockDecrypt>(kek: C, key: &[u8], expected: &[u8]) {
+        let mut out = [0; 40];
+        assert_eq!(wrap(&kek, key, &mut out).unwrap(), expected);
+
+        let mut unwrapped = [0; 32];
+        assert_eq!(unwrap(&kek, expected, &mut unwrapped).unwrap(), key);
+    }
+
+    check(Aes128::new_from_slice(&kek[..16]).unwrap(), &key[..16],
+        &hex!("1fa68b0a8112b447 aef34bd8fb5a7b82 9d3e862371d2cfe5"));
+    check(Aes192::new_from_slice(&kek[..24]).unwrap(), &key[..16],
+        &hex!("96778b25ae6ca435 f92b5b97c050aed2 468ab8a17ad84e5d"));
+    check(Aes256::new_from_slice(&kek).unwrap(), &key[..16],
+        &hex!("64e8c3f9ce0f5ba2 63e9777905818a2a 93c8191e7d6e8ae7"));
+    check(Aes192::new_from_slice(&kek[..24]).unwrap(), &key[..24],
+        &hex!("031d33264e15d332 68f24ec260743edc e1c6c7ddee725a93 6ba814915c6762d2"));
+    check(Aes256::new_from_slice(&kek).unwrap(), &key[..24],
+        &hex!("a8f9bc1612c68b3f f6e6f4fbe30e71e4 769c8b80a32cb895 8cd5d17d6b254da1"));
+    check(Aes256::new_from_slice(&kek).unwrap(), &key,
+        &hex!("28c9f404c4b810f4 cbccb35cfb87f826 3f5786e2d80ed326 cbc7f0e71a99f43b fb988b9b7a02dd21"));
+}
+
+// RFC 5649, section 6, and NIST samples for the single block case
+#[test]
+fn test_rfc5649_vectors(){
+    use aes::{Aes128, Aes192};
+    use cipher::KeyInit;
+
+    fn check<C: BlockEncrypt<BlockSize = U16> + BlockDecrypt>(kek: C, key: &[u8], expected: &[u8]) {
+        let mut out = [0; 40];
+        assert_eq!(wrap_padded(&kek, key, &mut out).unwrap(), expected);
+        assert_eq!(expected.len(), padded_wrapped_len(key.len()));
+
+        let mut unwrapped = [0; 32];
+        assert_eq!(unwrap_padded(&kek, expected, &mut unwrapped).unwrap(), key);
+    }
+
+    let kek = Aes192::new(&hex!("5840df6e29b02af1 ab493b705bf16ea1 ae8338f4dcc176a8").into());
+    check(kek.clone(), &hex!("c37b7e6492584340 bed1220780894115 5068f738"),
+        &hex!("138bdeaa9b8fa7fc 61f97742e72248ee 5ae6ae5360d1ae6a 5f54f373fa543b6a"));
+    check(kek, &hex!("466f7250617369"), &hex!("afbeb0f07dfbf541 9200f2ccb50bb24f"));
+
+    check(Aes128::new(&hex!("af83ae6624fc006da13b3c37b8a5933b").into()), &hex!("13126a"),
+        &hex!("a661f530339c9f344fa4755ad4cc3558"));
+    check(Aes128::new(&hex!("d19c43011c2a0242a38bd58b8d76456d").into()), &hex!("4202c90d7298cb4b"),
+        &hex!("65befaeaacbb4620d1a5d64e7b57a760"));
+    check(Aes128::new(&hex!("ebee1b9211aadefd06d258605f7134fb").into()),
+        &hex!("4029f7da4f8c29e4bb951a6f9d7f5305"),
+        &hex!("634194eaca80d77a21d11dd3e739dc5aa3feca2ce0990507"));
+}
+
+#[test]
+fn test_integrity_check(){
+    use crate::imp::{b128::Tekton128, Flags};
+    use rand::Rng;
+
+    let kek = Tekton128::new(rand::thread_rng().gen(), Flags::default());
+    let key: [u8; 24] = rand::thread_rng().gen();
+
+    let mut wrapped = [0; 32];
+    wrap(&kek, &key, &mut wrapped).unwrap();
+    for i in [0, 7, 8, 31] {
+        let mut bad = wrapped;
+        bad[i] ^= 1;
+        let mut out = [0xff; 24];
+        assert_eq!(unwrap(&kek, &bad, &mut out), Err(Error::IntegrityCheck));
+        assert_eq!(out, [0; 24]);
+    }
+
+    // every padded length, tampering with the length or the padding fails
+    for len in 1..=24 {
+        let mut wrapped = [0; 40];
+        let n = wrap_padded(&kek, &key[..len], &mut wrapped).unwrap().len();
+        let mut out = [0; 32];
+        assert_eq!(unwrap_padded(&kek, &wrapped[..n], &mut out).unwrap(), &key[..len]);
+
+        let mut bad = wrapped;
+        bad[n - 1] ^= 0x80;
+        assert_eq!(unwrap_padded(&kek, &bad[..n], &mut out), Err(Error::IntegrityCheck));
+        assert_eq!(out, [0; 32]);
+
+        // an unpadded wrap is not a padded one
+        if len % 8 == 0 && len >= 16 {
+            let mut plain = [0; 32];
+            wrap(&kek, &key[..len], &mut plain).unwrap();
+            assert_eq!(unwrap_padded(&kek, &plain[..len + 8], &mut out), Err(Error::IntegrityCheck));
+        }
+    }
+
+    let mut out = [0; 32];
+    assert_eq!(wrap(&kek, &key[..12], &mut out), Err(Error::InvalidLength));
+    assert_eq!(wrap(&kek, &key[..8], &mut out), Err(Error::InvalidLength));
+    assert_eq!(wrap(&kek, &key, &mut out[..31]), Err(Error::InvalidLength));
+    assert_eq!(wrap_padded(&kek, &[], &mut out), Err(Error::InvalidLength));
+    assert_eq!(unwrap(&kek, &wrapped[..16], &mut out), Err(Error::InvalidLength));
+    assert_eq!(unwrap_padded(&kek, &wrapped[..12], &mut out), Err(Error::InvalidLength));
+}
+
+#[test]
+fn test_tekton_keys(){
+    use crate::imp::{b128::Tekton128, b256::Tekton256, Flags, TektonCipher};
+    use rand::Rng;
+
+    let kek = Tekton128::new(rand::thread_rng().gen(), Flags::default());
+    let block: [u8; 16] = rand::thread_rng().gen();
+
+    let key: [u8; 32] = rand::thread_rng().gen();
+    let wrapped = wrap_tekton256_key(&kek, &key);
+    let unwrapped = unwrap_tekton256_key(&kek, &wrapped).unwrap();
+    assert_eq!(*unwrapped, key);
+
+    let (mut a, mut b) = (block, block);
+    TektonCipher::encrypt_block(&Tekton256::new(key, Flags::default()), &mut a);
+    TektonCipher::encrypt_block(&Tekton256::new(*unwrapped, Flags::default()), &mut b);
+    assert_eq!(a, b);
+
+    let key: [u8; 16] = rand::thread_rng().gen();
+    let wrapped = wrap_tekton128_key(&kek, &key);
+    assert_eq!(*unwrap_tekton128_key(&kek, &wrapped).unwrap(), key);
+
+    let other = Tekton128::new(rand::thread_rng().gen(), Flags::default());
+    assert_eq!(unwrap_tekton128_key(&other, &wrapped).err(), Some(Error::IntegrityCheck));
+}
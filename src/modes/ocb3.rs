@@ -0,0 +1,383 @@
+use core::marker::PhantomData;
+
+use aead::{
+    consts::{U0, U12, U16}, generic_array::{ArrayLength, GenericArray},
+    AeadCore, AeadInPlace, Key, KeyInit, KeySizeUser, Nonce, Tag,
+};
+use cipher::{BlockDecrypt, BlockEncrypt};
+use subtle::ConstantTimeEq;
+use zeroize::Zeroize;
+
+use super::{dbl, Block, BATCH, BLOCK_SIZE};
+
+
+/// Precomputed L_i, longer messages derive the rest on the fly.
+const L_TABLE: usize = 32;
+
+
+/// OCB3 (RFC 7253) with 96-bit nonces and `T` byte tags, 8 to 16. Every block
+/// costs one cipher call and independent blocks go through the batched path.
+#[derive(Clone)]
+pub struct Ocb3<C, T = U16> {
+    cipher: C,
+    l_star: u128,
+    l_dollar: u128,
+    l: [u128; L_TABLE],
+    tag: PhantomData<T>,
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>, T: ArrayLength<u8>> From<C> for Ocb3<C, T> {
+    fn from(cipher: C) -> Self {
+        let () = Self::VALID_TAG;
+
+        let mut l_star = Block::default();
+        cipher.encrypt_block(&mut l_star);
+        let l_star = u128::from_be_bytes(l_star.into());
+        let l_dollar = dbl(l_star);
+
+        let mut l = [0; L_TABLE];
+        l[0] = dbl(l_dollar);
+        for i in 1..L_TABLE {
+            l[i] = dbl(l[i - 1]);
+        }
+        Ocb3 { cipher, l_star, l_dollar, l, tag: PhantomData }
+    }
+}
+
+impl<C: KeySizeUser, T> KeySizeUser for Ocb3<C, T> {
+    type KeySize = C::KeySize;
+}
+
+impl<C: BlockEncrypt<BlockSize = U16> + KeyInit, T: ArrayLength<u8>> KeyInit for Ocb3<C, T> {
+    fn new(key: &Key<Self>) -> Self {
+        C::new(key).into()
+    }
+}
+
+impl<C, T: ArrayLength<u8>> AeadCore for Ocb3<C, T> {
+    type NonceSize = U12;
+    type TagSize = T;
+    type CiphertextOverhead = U0;
+}
+
+impl<C, T> AeadInPlace for Ocb3<C, T>
+where
+    C: BlockEncrypt<BlockSize = U16> + BlockDecrypt,
+    T: ArrayLength<u8>,
+{
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> aead::Result<Tag<Self>> {
+        let mut offset = self.initial_offset(nonce);
+        let mut checksum = 0;
+        let (blocks, tail) = buffer.as_chunks_mut::<BLOCK_SIZE>();
+
+        let mut i = 0;
+        for chunk in blocks.chunks_mut(BATCH) {
+            let mut offsets = [0; BATCH];
+            self.offsets(&mut offset, &mut i, &mut offsets[..chunk.len()]);
+
+            let mut batch = [Block::default(); BATCH];
+            for ((b, p), o) in batch.iter_mut().zip(chunk.iter()).zip(&offsets) {
+                let p = u128::from_be_bytes(*p);
+                checksum ^= p;
+                *b = (p ^ o).to_be_bytes().into();
+            }
+            self.cipher.encrypt_blocks(&mut batch[..chunk.len()]);
+            for ((c, b), o) in chunk.iter_mut().zip(&batch).zip(&offsets) {
+                *c = (u128::from_be_bytes((*b).into()) ^ o).to_be_bytes();
+            }
+        }
+
+        if !tail.is_empty() {
+            offset ^= self.l_star;
+            let pad = self.encipher(offset);
+            checksum ^= padded(tail);
+            for (c, k) in tail.iter_mut().zip(pad.to_be_bytes()) {
+                *c ^= k;
+            }
+        }
+
+        Ok(self.tag(checksum, offset, associated_data))
+    }
+
+    /// On a tag mismatch the buffer is restored to the ciphertext.
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &Tag<Self>,
+    ) -> aead::Result<()> {
+        let mut offset = self.initial_offset(nonce);
+        let mut checksum = 0;
+        let (blocks, tail) = buffer.as_chunks_mut::<BLOCK_SIZE>();
+
+        let mut i = 0;
+        for chunk in blocks.chunks_mut(BATCH) {
+            let mut offsets = [0; BATCH];
+            self.offsets(&mut offset, &mut i, &mut offsets[..chunk.len()]);
+
+            let mut batch = [Block::default(); BATCH];
+            for ((b, c), o) in batch.iter_mut().zip(chunk.iter()).zip(&offsets) {
+                *b = (u128::from_be_bytes(*c) ^ o).to_be_bytes().into();
+            }
+            self.cipher.decrypt_blocks(&mut batch[..chunk.len()]);
+            for ((p, b), o) in chunk.iter_mut().zip(&batch).zip(&offsets) {
+                let plain = u128::from_be_bytes((*b).into()) ^ o;
+                checksum ^= plain;
+                *p = plain.to_be_bytes();
+            }
+        }
+
+        if !tail.is_empty() {
+            offset ^= self.l_star;
+            let pad = self.encipher(offset);
+            for (p, k) in tail.iter_mut().zip(pad.to_be_bytes()) {
+                *p ^= k;
+            }
+            checksum ^= padded(tail);
+        }
+
+        let expected = self.tag(checksum, offset, associated_data);
+        if expected.ct_eq(tag).into() {
+            Ok(())
+        } else {
+            self.encrypt_in_place_detached(nonce, associated_data, buffer)?;
+            Err(aead::Error)
+        }
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>, T: ArrayLength<u8>> Ocb3<C, T> {
+
+    const VALID_TAG: () = assert!(T::USIZE >= 8 && T::USIZE <= 16, "OCB3 tags are 8 to 16 bytes");
+
+    fn initial_offset(&self, nonce: &[u8]) -> u128 {
+        let mut n = [0; 16];
+        n[4..].copy_from_slice(nonce);
+        let n = u128::from_be_bytes(n) | ((T::USIZE as u128 * 8) % 128) << 121 | 1 << 96;
+
+        let bottom = (n & 63) as u32;
+        let ktop = self.encipher(n & !63);
+        let stretch = (ktop >> 64) as u64 ^ (ktop >> 56) as u64;
+        if bottom == 0 {
+            ktop
+        } else {
+            ktop << bottom | (stretch >> (64 - bottom)) as u128
+        }
+    }
+
+    // Offset_i = Offset_{i-1} ^ L_{ntz(i)} for the next `out.len()` blocks
+    #[inline]
+    fn offsets(&self, offset: &mut u128, i: &mut u64, out: &mut [u128]) {
+        for o in out {
+            *i += 1;
+            *offset ^= self.l(i.trailing_zeros() as usize);
+            *o = *offset;
+        }
+    }
+
+    fn l(&self, n: usize) -> u128 {
+        if n < L_TABLE {
+            return self.l[n];
+        }
+        (L_TABLE..=n).fold(self.l[L_TABLE - 1], |l, _| dbl(l))
+    }
+
+    fn hash(&self, associated_data: &[u8]) -> u128 {
+        let mut sum = 0;
+        let mut offset = 0;
+        let (blocks, tail) = associated_data.as_chunks::<BLOCK_SIZE>();
+
+        let mut i = 0;
+        for chunk in blocks.chunks(BATCH) {
+            let mut offsets = [0; BATCH];
+            self.offsets(&mut offset, &mut i, &mut offsets[..chunk.len()]);
+
+            let mut batch = [Block::default(); BATCH];
+            for ((b, a), o) in batch.iter_mut().zip(chunk).zip(&offsets) {
+                *b = (u128::from_be_bytes(*a) ^ o).to_be_bytes().into();
+            }
+            self.cipher.encrypt_blocks(&mut batch[..chunk.len()]);
+            for b in &batch[..chunk.len()] {
+                sum ^= u128::from_be_bytes((*b).into());
+            }
+        }
+
+        if !tail.is_empty() {
+            offset ^= self.l_star;
+            sum ^= self.encipher(padded(tail) ^ offset);
+        }
+        sum
+    }
+
+    fn tag(&self, checksum: u128, offset: u128, associated_data: &[u8]) -> Tag<Self> {
+        let full = self.encipher(checksum ^ offset ^ self.l_dollar) ^ self.hash(associated_data);
+        GenericArray::clone_from_slice(&full.to_be_bytes()[..T::USIZE])
+    }
+
+    #[inline]
+    fn encipher(&self, x: u128) -> u128 {
+        let mut block = Block::from(x.to_be_bytes());
+        self.cipher.encrypt_block(&mut block);
+        u128::from_be_bytes(block.into())
+    }
+}
+
+impl<C, T> Drop for Ocb3<C, T> {
+    fn drop(&mut self) {
+        self.l_star.zeroize();
+        self.l_dollar.zeroize();
+        self.l.zeroize();
+    }
+}
+
+/// A partial block followed by 10*.
+fn padded(tail: &[u8]) -> u128 {
+    let mut block = [0; 16];
+    block[..tail.len()].copy_from_slice(tail);
+    block[tail.len()] = 0x80;
+    u128::from_be_bytes(block)
+}
+
+
+#[cfg(test)]
+use hex_literal::hex;
+
+// RFC 7253, appendix A. A and P are prefixes of 00 01 02 ..
+#[test]
+fn test_aes_vectors(){
+    use aes::Aes128;
+
+    let vectors: [(u8, usize, usize, &[u8]); 16] = [
+        (0x00, 0, 0, &hex!("785407bfffc8ad9edcc5520ac9111ee6")),
+        (0x01, 8, 8, &hex!("6820b3657b6f615a5725bda0d3b4eb3a257c9af1f8f03009")),
+        (0x02, 8, 0, &hex!("81017f8203f081277152fade694a0a00")),
+        (0x03, 0, 8, &hex!("45dd69f8f5aae72414054cd1f35d82760b2cd00d2f99bfa9")),
+        (0x04, 16, 16, &hex!("571d535b60b277188be5147170a9a22c3ad7a4ff3835b8c5701c1ccec8fc3358")),
+        (0x05, 16, 0, &hex!("8cf761b6902ef764462ad86498ca6b97")),
+        (0x06, 0, 16, &hex!("5ce88ec2e0692706a915c00aeb8b2396f40e1c743f52436bdf06d8fa1eca343d")),
+        (0x07, 24, 24, &hex!("1ca2207308c87c010756104d8840ce1952f09673a448a122c92c62241051f57356d7f3c90bb0e07f")),
+        (0x08, 24, 0, &hex!("6dc225a071fc1b9f7c69f93b0f1e10de")),
+        (0x09, 0, 24, &hex!("221bd0de7fa6fe993eccd769460a0af2d6cded0c395b1c3ce725f32494b9f914d85c0b1eb38357ff")),
+        (0x0a, 32, 32, &hex!(
+            "bd6f6c496201c69296c11efd138a467abd3c707924b964deaffc40319af5a485"
+            "40fbba186c5553c68ad9f592a79a4240"
+        )),
+        (0x0b, 32, 0, &hex!("fe80690bee8a485d11f32965bc9d2a32")),
+        (0x0c, 0, 32, &hex!(
+            "2942bfc773bda23cabc6acfd9bfd5835bd300f0973792ef46040c53f1432bcdf"
+            "b5e1dde3bc18a5f840b52e653444d5df"
+        )),
+        (0x0d, 40, 40, &hex!(
+            "d5ca91748410c1751ff8a2f618255b68a0a12e093ff454606e59f9c1d0ddc54b"
+            "65e8628e568bad7aed07ba06a4a69483a7035490c5769e60"
+        )),
+        (0x0e, 40, 0, &hex!("c5cd9d1850c141e358649994ee701b68")),
+        (0x0f, 0, 40, &hex!(
+            "4412923493c57d5de0d700f753cce0d1d2d95060122e9f15a5ddbfc5787e50b5"
+            "cc55ee507bcb084e479ad363ac366b95a98ca5f3000b1479"
+        )),
+    ];
+
+    let ocb = Ocb3::<Aes128>::new(&hex!("000102030405060708090a0b0c0d0e0f").into());
+    let data: [u8; 40] = core::array::from_fn(|i| i as u8);
+
+    for (n, a_len, p_len, expected) in vectors {
+        let mut nonce = hex!("bbaa99887766554433221100");
+        nonce[11] = n;
+        let (ciphertext, tag) = expected.split_at(p_len);
+
+        let mut buffer = data[..p_len].to_vec();
+        let t = ocb.encrypt_in_place_detached(&nonce.into(), &data[..a_len], &mut buffer).unwrap();
+        assert_eq!(buffer, ciphertext);
+        assert_eq!(t.as_slice(), tag);
+
+        ocb.decrypt_in_place_detached(&nonce.into(), &data[..a_len], &mut buffer, &t).unwrap();
+        assert_eq!(buffer, data[..p_len]);
+    }
+
+    // 96-bit tag example
+    let ocb = Ocb3::<Aes128, aead::consts::U12>::new(&hex!("0f0e0d0c0b0a09080706050403020100").into());
+    let mut buffer = data.to_vec();
+    let tag = ocb.encrypt_in_place_detached(&hex!("bbaa9988776655443322110d").into(), &data, &mut buffer).unwrap();
+    assert_eq!([&buffer[..], &tag].concat(), hex!(
+        "1792a4e31e0755fb03e31b22116e6c2ddf9efd6e33d536f1a0124b0a55bae884"
+        "ed93481529c76b6ad0c515f4d1cdd4fdac4f02aa"
+    ));
+}
+
+// The iterated "more sample results" of RFC 7253, appendix A, for every tag length
+#[test]
+fn test_aes_sample_results(){
+    use aes::{Aes128, Aes256};
+    use aead::consts::{U8, U12};
+
+    fn sample<C, T>(expected: &[u8])
+    where
+        C: BlockEncrypt<BlockSize = U16> + BlockDecrypt + KeyInit,
+        T: ArrayLength<u8>,
+    {
+        let mut key = Key::<C>::default();
+        let last = key.len() - 1;
+        key[last] = T::USIZE as u8 * 8;
+        let ocb = Ocb3::<C, T>::new(&key);
+
+        let nonce = |n: u32| {
+            let mut nonce = Nonce::<Ocb3<C, T>>::default();
+            nonce[8..].copy_from_slice(&n.to_be_bytes());
+            nonce
+        };
+
+        let mut c = [0; 22_400];
+        let mut len = 0;
+        for i in 0..128 {
+            let s = [0; 128];
+            let s = &s[..i];
+            let mut push = |nonce, ad: &[u8], msg: &[u8]| {
+                c[len..len + msg.len()].copy_from_slice(msg);
+                let tag = ocb.encrypt_in_place_detached(&nonce, ad, &mut c[len..len + msg.len()]).unwrap();
+                len += msg.len();
+                c[len..len + T::USIZE].copy_from_slice(&tag);
+                len += T::USIZE;
+            };
+            push(nonce(3 * i as u32 + 1), s, s);
+            push(nonce(3 * i as u32 + 2), &[], s);
+            push(nonce(3 * i as u32 + 3), s, &[]);
+        }
+
+        let tag = ocb.encrypt_in_place_detached(&nonce(385), &c[..len], &mut []).unwrap();
+        assert_eq!(tag.as_slice(), expected);
+    }
+
+    sample::<Aes128, U16>(&hex!("67e944d23256c5e0b6c61fa22fdf1ea2"));
+    sample::<Aes256, U16>(&hex!("d90eb8e9c977c88b79dd793d7ffa161c"));
+    sample::<Aes128, U12>(&hex!("77a3d8e73589158d25d01209"));
+    sample::<Aes256, U12>(&hex!("5458359ac23b0cba9e6330dd"));
+    sample::<Aes128, U8>(&hex!("192c9b7bd90ba06a"));
+    sample::<Aes256, U8>(&hex!("7d4ea5d445501cbe"));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_tekton(){
+    use crate::imp::{b128::Tekton128, b256::Tekton256};
+    use aead::{Aead, consts::U8};
+    use rand::Rng;
+
+    let key: [u8; 16] = rand::thread_rng().gen();
+    super::check_aead(&Ocb3::<Tekton128>::new(&key.into()));
+    super::check_aead(&Ocb3::<Tekton128, U8>::new(&key.into()));
+    super::check_aead(&Ocb3::<Tekton256>::new(&rand::thread_rng().gen::<[u8; 32]>().into()));
+
+    // the tag length goes into the nonce, a short tag is no prefix of a long one
+    let nonce = Nonce::<Ocb3<Tekton128>>::from(rand::thread_rng().gen::<[u8; 12]>());
+    let long = Ocb3::<Tekton128>::new(&key.into()).encrypt(&nonce, &[0; 40][..]).unwrap();
+    let short = Ocb3::<Tekton128, U8>::new(&key.into()).encrypt(&nonce, &[0; 40][..]).unwrap();
+    assert_ne!(long[..40], short[..40]);
+    assert_ne!(long[40..48], short[40..]);
+}
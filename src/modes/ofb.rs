@@ -0,0 +1,104 @@
+use cipher::{
+    consts::{U1, U16}, BlockEncrypt, BlockSizeUser, ParBlocksSizeUser, StreamBackend,
+    StreamCipher, StreamCipherCore, StreamCipherCoreWrapper, StreamCipherError,
+    StreamClosure, inout::InOutBuf,
+};
+
+use super::Block;
+
+
+/// Block level OFB, `Ofb` adds the byte buffering on top.
+#[derive(Clone)]
+pub struct OfbCore<C> {
+    cipher: C,
+    register: Block,
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> OfbCore<C> {
+
+    pub fn new(cipher: C, iv: &[u8; 16]) -> Self {
+        OfbCore { cipher, register: Block::from(*iv) }
+    }
+}
+
+impl<C> BlockSizeUser for OfbCore<C> {
+    type BlockSize = U16;
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> StreamCipherCore for OfbCore<C> {
+    // the keystream cycles eventually, but not at a known point
+    fn remaining_blocks(&self) -> Option<usize> {
+        None
+    }
+
+    fn process_with_backend(&mut self, f: impl StreamClosure<BlockSize = Self::BlockSize>) {
+        f.call(self);
+    }
+}
+
+// every keystream block depends on the previous one, nothing to parallelize
+impl<C> ParBlocksSizeUser for OfbCore<C> {
+    type ParBlocksSize = U1;
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> StreamBackend for OfbCore<C> {
+    #[inline]
+    fn gen_ks_block(&mut self, block: &mut Block) {
+        self.cipher.encrypt_block(&mut self.register);
+        *block = self.register;
+    }
+}
+
+
+/// OFB mode, encryption and decryption are the same keystream XOR. Data can
+/// be fed in pieces of any length.
+#[derive(Clone)]
+pub struct Ofb<C: BlockEncrypt<BlockSize = U16>> {
+    inner: StreamCipherCoreWrapper<OfbCore<C>>,
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> Ofb<C> {
+
+    pub fn new(cipher: C, iv: &[u8; 16]) -> Self {
+        Ofb { inner: StreamCipherCoreWrapper::from_core(OfbCore::new(cipher, iv)) }
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> StreamCipher for Ofb<C> {
+    #[inline]
+    fn try_apply_keystream_inout(&mut self, buf: InOutBuf<'_, '_, u8>) -> Result<(), StreamCipherError> {
+        self.inner.try_apply_keystream_inout(buf)
+    }
+}
+
+
+#[cfg(test)]
+use hex_literal::hex;
+
+// NIST SP 800-38A, F.4.1 and F.4.2
+#[test]
+fn test_aes_vectors(){
+    use aes::Aes128;
+    use cipher::KeyInit;
+
+    let aes = Aes128::new(&hex!("2b7e151628aed2a6abf7158809cf4f3c").into());
+    let iv = hex!("000102030405060708090a0b0c0d0e0f");
+    let plain = hex!(
+        "6bc1bee22e409f96e93d7e117393172a ae2d8a571e03ac9c9eb76fac45af8e51"
+        "30c81c46a35ce411e5fbc1191a0a52ef f69f2445df4f9b17ad2b417be66c3710"
+    );
+    let expected = hex!(
+        "3b3fd92eb72dad20333449f8e83cfb4a 7789508d16918f03f53c52dac54ed825"
+        "9740051e9c5fecf64344f7a82260edcc 304c6528f659c77866a510d9c1d6ae5e"
+    );
+
+    let mut data = plain;
+    Ofb::new(aes.clone(), &iv).apply_keystream(&mut data);
+    assert_eq!(data, expected);
+
+    let mut ofb = Ofb::new(aes, &iv);
+    for chunk in data.chunks_mut(5) {
+        ofb.apply_keystream(chunk);
+    }
+    assert_eq!(data, plain);
+}
@@ -0,0 +1,90 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use super::{Error, BLOCK_SIZE};
+
+
+/// Length of `len` bytes after PKCS#7 padding, always at least one byte longer.
+pub fn padded_len(len: usize) -> usize {
+    (len / BLOCK_SIZE + 1) * BLOCK_SIZE
+}
+
+/// Pads the first `len` bytes of `buf` in place and returns them with the padding.
+pub fn pad(buf: &mut [u8], len: usize) -> Result<&mut [u8], Error> {
+    let end = padded_len(len);
+    if end > buf.len() {
+        return Err(Error::InvalidLength);
+    }
+    buf[len..end].fill((end - len) as u8);
+    Ok(&mut buf[..end])
+}
+
+#[cfg(feature = "alloc")]
+pub fn pad_vec(data: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(padded_len(data.len()));
+    buf.extend_from_slice(data);
+    buf.resize(padded_len(data.len()), 0);
+    pad(&mut buf, data.len()).expect("buffer sized by padded_len");
+    buf
+}
+
+/// Strips PKCS#7 padding. The last block is checked in full whatever the
+/// padding byte says, so a failing check does not reveal where it failed.
+pub fn unpad(data: &[u8]) -> Result<&[u8], Error> {
+    if data.is_empty() || !data.len().is_multiple_of(BLOCK_SIZE) {
+        return Err(Error::InvalidLength);
+    }
+    let last = &data[data.len() - BLOCK_SIZE..];
+    let n = last[BLOCK_SIZE - 1];
+
+    let mut bad = (n == 0) as u8 | (n as usize > BLOCK_SIZE) as u8;
+    for (i, &b) in last.iter().rev().enumerate() {
+        let in_padding = ((i as u8) < n) as u8;
+        bad |= in_padding & (b != n) as u8;
+    }
+
+    if bad != 0 {
+        return Err(Error::InvalidPadding);
+    }
+    Ok(&data[..data.len() - n as usize])
+}
+
+
+#[test]
+fn test_pad_unpad(){
+    let mut buf = [0xaa; 64];
+    for len in 0..48 {
+        let padded = pad(&mut buf, len).unwrap();
+        assert_eq!(padded.len(), padded_len(len));
+        assert_eq!(padded.len() % BLOCK_SIZE, 0);
+        assert!(padded[len..].iter().all(|&b| b as usize == padded.len() - len));
+        assert_eq!(unpad(padded).unwrap().len(), len);
+    }
+
+    assert_eq!(pad(&mut [0; 16], 16), Err(Error::InvalidLength));
+
+    let mut block = [0; 16];
+    assert_eq!(pad(&mut block, 15).unwrap()[15], 1);
+}
+
+#[test]
+fn test_unpad_rejects_malformed(){
+    let mut block = [16; 16];
+    assert_eq!(unpad(&block).unwrap(), &[] as &[u8]);
+
+    block[15] = 0;
+    assert_eq!(unpad(&block), Err(Error::InvalidPadding));
+
+    block[15] = 17;
+    assert_eq!(unpad(&block), Err(Error::InvalidPadding));
+
+    block = [4; 16];
+    block[12] = 5;
+    assert_eq!(unpad(&block), Err(Error::InvalidPadding));
+    block[11] = 3;
+    block[12] = 4;
+    assert_eq!(unpad(&block).unwrap().len(), 12);
+
+    assert_eq!(unpad(&[]), Err(Error::InvalidLength));
+    assert_eq!(unpad(&[1; 15]), Err(Error::InvalidLength));
+}
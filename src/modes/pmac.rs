@@ -0,0 +1,272 @@
+use cipher::{consts::U16, BlockEncrypt, Key, KeySizeUser};
+use digest::{FixedOutput, FixedOutputReset, MacMarker, Output, OutputSizeUser};
+use zeroize::Zeroize;
+
+use super::{dbl, Block, BATCH, BLOCK_SIZE};
+
+
+/// Precomputed L(i), longer messages derive the rest on the fly.
+const L_TABLE: usize = 32;
+
+
+/// PMAC1 (Black and Rogaway) behind the `digest::Mac` traits. Unlike `Cmac`
+/// every block but the last is enciphered independently, so whole batches go
+/// through the cipher's multi-block path. Construction and truncation work
+/// as for `Cmac`.
+#[derive(Clone)]
+pub struct Pmac<C> {
+    cipher: C,
+    l: [u128; L_TABLE],
+    l_inv: u128,
+    offset: u128,
+    sum: u128,
+    // blocks queued or enciphered so far
+    count: u64,
+    batch: [Block; BATCH],
+    queued: usize,
+    // the last block is held back until `finalize`, it is not enciphered
+    buffer: [u8; 16],
+    pos: usize,
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> From<C> for Pmac<C> {
+    fn from(cipher: C) -> Self {
+        let mut l0 = Block::default();
+        cipher.encrypt_block(&mut l0);
+        let l0 = u128::from_be_bytes(l0.into());
+
+        let mut l = [l0; L_TABLE];
+        for i in 1..L_TABLE {
+            l[i] = dbl(l[i - 1]);
+        }
+        Pmac {
+            cipher,
+            l,
+            l_inv: half(l0),
+            offset: 0,
+            sum: 0,
+            count: 0,
+            batch: [Block::default(); BATCH],
+            queued: 0,
+            buffer: [0; 16],
+            pos: 0,
+        }
+    }
+}
+
+impl<C: KeySizeUser> KeySizeUser for Pmac<C> {
+    type KeySize = C::KeySize;
+}
+
+impl<C: BlockEncrypt<BlockSize = U16> + cipher::KeyInit> cipher::KeyInit for Pmac<C> {
+    fn new(key: &Key<Self>) -> Self {
+        C::new(key).into()
+    }
+}
+
+impl<C> OutputSizeUser for Pmac<C> {
+    type OutputSize = U16;
+}
+
+impl<C> MacMarker for Pmac<C> {}
+
+impl<C: BlockEncrypt<BlockSize = U16>> digest::Update for Pmac<C> {
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            if self.pos == BLOCK_SIZE {
+                self.push(self.buffer);
+                self.pos = 0;
+            }
+            // whole blocks skip the buffer, the last one is still held back
+            while self.pos == 0 && data.len() > BLOCK_SIZE {
+                let (block, rest) = data.split_at(BLOCK_SIZE);
+                self.push(block.try_into().unwrap());
+                data = rest;
+            }
+            let n = data.len().min(BLOCK_SIZE - self.pos);
+            self.buffer[self.pos..self.pos + n].copy_from_slice(&data[..n]);
+            self.pos += n;
+            data = &data[n..];
+        }
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> FixedOutput for Pmac<C> {
+    fn finalize_into(mut self, out: &mut Output<Self>) {
+        self.finalize_into_reset(out);
+    }
+}
+
+impl<C> digest::Reset for Pmac<C> {
+    fn reset(&mut self) {
+        self.offset = 0;
+        self.sum = 0;
+        self.count = 0;
+        for block in &mut self.batch {
+            block.as_mut_slice().zeroize();
+        }
+        self.queued = 0;
+        self.buffer.zeroize();
+        self.pos = 0;
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> FixedOutputReset for Pmac<C> {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        self.flush();
+        let mut last = self.sum;
+        if self.pos == BLOCK_SIZE {
+            last ^= u128::from_be_bytes(self.buffer) ^ self.l_inv;
+        } else {
+            let mut padded = [0; 16];
+            padded[..self.pos].copy_from_slice(&self.buffer[..self.pos]);
+            padded[self.pos] = 0x80;
+            last ^= u128::from_be_bytes(padded);
+        }
+        *out = last.to_be_bytes().into();
+        self.cipher.encrypt_block(out);
+        last.zeroize();
+        digest::Reset::reset(self);
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> Pmac<C> {
+
+    /// Queues block i ^ L(ntz(i)) for enciphering.
+    #[inline]
+    fn push(&mut self, block: [u8; 16]) {
+        self.count += 1;
+        self.offset ^= self.l(self.count.trailing_zeros() as usize);
+        self.batch[self.queued] = (u128::from_be_bytes(block) ^ self.offset).to_be_bytes().into();
+        self.queued += 1;
+        if self.queued == BATCH {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        let batch = &mut self.batch[..self.queued];
+        self.cipher.encrypt_blocks(batch);
+        for block in batch.iter() {
+            self.sum ^= u128::from_be_bytes((*block).into());
+        }
+        self.queued = 0;
+    }
+
+    fn l(&self, n: usize) -> u128 {
+        if n < L_TABLE {
+            return self.l[n];
+        }
+        (L_TABLE..=n).fold(self.l[L_TABLE - 1], |l, _| dbl(l))
+    }
+}
+
+impl<C> Drop for Pmac<C> {
+    fn drop(&mut self) {
+        self.l.zeroize();
+        self.l_inv.zeroize();
+        self.offset.zeroize();
+        self.sum.zeroize();
+        for block in &mut self.batch {
+            block.as_mut_slice().zeroize();
+        }
+        self.buffer.zeroize();
+    }
+}
+
+/// Inverse of `dbl`, L * x^-1.
+fn half(x: u128) -> u128 {
+    (x >> 1) ^ ((x & 1) * (1 << 127 | 0x43))
+}
+
+
+#[cfg(test)]
+use digest::Mac;
+
+#[cfg(test)]
+use hex_literal::hex;
+
+// http://web.cs.ucdavis.edu/~rogaway/ocb/pmac-test.htm
+#[test]
+fn test_aes_vectors(){
+    use aes::{Aes128, Aes256};
+
+    let msg: [u8; 34] = core::array::from_fn(|i| i as u8);
+
+    let vectors: [(&[u8], [u8; 16]); 7] = [
+        (&[], hex!("4399572cd6ea5341b8d35876a7098af7")),
+        (&msg[..3], hex!("256ba5193c1b991b4df0c51f388a9e27")),
+        (&msg[..16], hex!("ebbd822fa458daf6dfdad7c27da76338")),
+        (&msg[..20], hex!("0412ca150bbf79058d8c75a58c993f55")),
+        (&msg[..32], hex!("e97ac04e9e5e3399ce5355cd7407bc75")),
+        (&msg, hex!("5cba7d5eb24f7c86ccc54604e53d5512")),
+        (&[0; 1000], hex!("c2c9fa1d9985f6f0d2aff915a0e8d910")),
+    ];
+    let key: [u8; 16] = core::array::from_fn(|i| i as u8);
+    let mut pmac = Pmac::<Aes128>::new(&key.into());
+    for (data, tag) in vectors {
+        pmac.update(data);
+        assert_eq!(pmac.finalize_reset().into_bytes(), tag.into());
+    }
+
+    let vectors: [(&[u8], [u8; 16]); 7] = [
+        (&[], hex!("e620f52fe75bbe87ab758c0624943d8b")),
+        (&msg[..3], hex!("ffe124cc152cfb2bf1ef5409333c1c9a")),
+        (&msg[..16], hex!("853fdbf3f91dcd36380d698a64770bab")),
+        (&msg[..20], hex!("7711395fbe9dec19861aeb96e052cd1b")),
+        (&msg[..32], hex!("08fa25c28678c84d383130653e77f4c0")),
+        (&msg, hex!("edd8a05f4b66761f9eee4feb4ed0c3a1")),
+        (&[0; 1000], hex!("69aa77f231eb0cdff960f5561d29a96e")),
+    ];
+    let key: [u8; 32] = core::array::from_fn(|i| i as u8);
+    let pmac = Pmac::<Aes256>::new(&key.into());
+    for (data, tag) in vectors {
+        pmac.clone().chain_update(data).verify(&tag.into()).unwrap();
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_chunking(){
+    use crate::imp::{b128::Tekton128, b256::Tekton256};
+    use rand::Rng;
+
+    fn check<C: BlockEncrypt<BlockSize = U16> + cipher::KeyInit + Clone>(key: &Key<C>) {
+        // several full batches plus a partial one
+        let mut msg = vec![0u8; 16 * 70 + 5];
+        rand::thread_rng().fill(&mut msg[..]);
+        let pmac = Pmac::<C>::new(key);
+
+        for len in [0, 1, 16, 17, 255, 256, 257, msg.len()] {
+            let tag = pmac.clone().chain_update(&msg[..len]).finalize().into_bytes();
+
+            for chunk in [1, 3, 15, 16, 17, 100, 256] {
+                let mut m = pmac.clone();
+                for part in msg[..len].chunks(chunk) {
+                    m.update(part);
+                }
+                m.verify_slice(&tag).unwrap();
+            }
+
+            let mut m = pmac.clone();
+            let mut rest = &msg[..len];
+            while !rest.is_empty() {
+                let (part, tail) = rest.split_at(rand::thread_rng().gen_range(0..=rest.len()));
+                m.update(part);
+                rest = tail;
+            }
+            m.verify_slice(&tag).unwrap();
+
+            pmac.clone().chain_update(&msg[..len]).verify_truncated_left(&tag[..8]).unwrap();
+
+            if len > 0 {
+                let mut bad = msg[..len].to_vec();
+                bad[len / 2] ^= 1;
+                assert!(pmac.clone().chain_update(&bad).verify_slice(&tag).is_err());
+            }
+        }
+    }
+
+    check::<Tekton128>(&rand::thread_rng().gen::<[u8; 16]>().into());
+    check::<Tekton256>(&rand::thread_rng().gen::<[u8; 32]>().into());
+}
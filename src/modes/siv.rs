@@ -0,0 +1,351 @@
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+
+use core::ops::Add;
+
+use aead::{
+    consts::{U0, U16}, generic_array::{typenum::Sum, ArrayLength, GenericArray},
+    AeadCore, AeadInPlace, Buffer, Key, KeyInit, KeySizeUser, Nonce, Tag,
+};
+use cipher::{BlockEncrypt, StreamCipher};
+use digest::Mac;
+use subtle::ConstantTimeEq;
+
+use super::cmac::Cmac;
+use super::ctr::{Ctr, Split};
+use super::{dbl, BLOCK_SIZE};
+
+
+/// S2V takes at most 127 strings, the plaintext is the last one.
+pub const MAX_HEADERS: usize = 126;
+
+
+/// SIV (RFC 5297), deterministic authenticated encryption with any number of
+/// associated data components. Encrypting the same headers and plaintext twice
+/// gives the same ciphertext and nothing else leaks, so a repeated or missing
+/// nonce is not fatal. A nonce, if any, goes in as the last header.
+#[derive(Clone)]
+pub struct Siv<C> {
+    mac: Cmac<C>,
+    ctr: C,
+}
+
+impl<C: BlockEncrypt<BlockSize = U16> + Clone> Siv<C> {
+
+    /// `mac` keys S2V, `ctr` the encryption, their keys must be independent.
+    pub fn from_ciphers(mac: C, ctr: C) -> Self {
+        Siv { mac: Cmac::from(mac), ctr }
+    }
+
+    /// Encrypts `buffer` in place and returns the synthetic IV, which doubles as the tag.
+    pub fn encrypt_in_place_detached<I, T>(&self, headers: I, buffer: &mut [u8]) -> Result<[u8; 16], aead::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        let v = self.s2v(headers, buffer)?;
+        self.keystream(&v).apply_keystream(buffer);
+        Ok(v)
+    }
+
+    /// Decrypts `buffer` in place, on a tag mismatch it is restored to the ciphertext.
+    pub fn decrypt_in_place_detached<I, T>(&self, headers: I, buffer: &mut [u8], tag: &[u8; 16]) -> Result<(), aead::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        self.keystream(tag).apply_keystream(buffer);
+        match self.s2v(headers, buffer) {
+            Ok(v) if bool::from(v.ct_eq(tag)) => Ok(()),
+            _ => {
+                self.keystream(tag).apply_keystream(buffer);
+                Err(aead::Error)
+            }
+        }
+    }
+
+    /// Returns the synthetic IV followed by the ciphertext, as laid out in RFC 5297.
+    #[cfg(feature = "alloc")]
+    pub fn encrypt<I, T>(&self, headers: I, plaintext: &[u8]) -> Result<Vec<u8>, aead::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        let mut out = Vec::with_capacity(BLOCK_SIZE + plaintext.len());
+        out.extend_from_slice(&[0; BLOCK_SIZE]);
+        out.extend_from_slice(plaintext);
+        let v = self.encrypt_in_place_detached(headers, &mut out[BLOCK_SIZE..])?;
+        out[..BLOCK_SIZE].copy_from_slice(&v);
+        Ok(out)
+    }
+
+    #[cfg(feature = "alloc")]
+    pub fn decrypt<I, T>(&self, headers: I, ciphertext: &[u8]) -> Result<Vec<u8>, aead::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        if ciphertext.len() < BLOCK_SIZE {
+            return Err(aead::Error);
+        }
+        let (v, c) = ciphertext.split_at(BLOCK_SIZE);
+        let mut out = c.to_vec();
+        self.decrypt_in_place_detached(headers, &mut out, v.try_into().unwrap())?;
+        Ok(out)
+    }
+
+    fn s2v<I, T>(&self, headers: I, plaintext: &[u8]) -> Result<[u8; 16], aead::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<[u8]>,
+    {
+        let mut d = u128::from_be_bytes(self.mac(&[0; 16]));
+        for (i, header) in headers.into_iter().enumerate() {
+            if i == MAX_HEADERS {
+                return Err(aead::Error);
+            }
+            d = dbl(d) ^ u128::from_be_bytes(self.mac(header.as_ref()));
+        }
+
+        let mut mac = self.mac.clone();
+        if plaintext.len() >= BLOCK_SIZE {
+            // xorend, D goes into the last 16 bytes
+            let (head, tail) = plaintext.split_at(plaintext.len() - BLOCK_SIZE);
+            mac.update(head);
+            let tail = u128::from_be_bytes(tail.try_into().unwrap());
+            mac.update(&(tail ^ d).to_be_bytes());
+        } else {
+            let mut padded = [0; 16];
+            padded[..plaintext.len()].copy_from_slice(plaintext);
+            padded[plaintext.len()] = 0x80;
+            mac.update(&(dbl(d) ^ u128::from_be_bytes(padded)).to_be_bytes());
+        }
+        Ok(mac.finalize().into_bytes().into())
+    }
+
+    fn mac(&self, data: &[u8]) -> [u8; 16] {
+        let mut mac = self.mac.clone();
+        mac.update(data);
+        mac.finalize().into_bytes().into()
+    }
+
+    // bits 31 and 63 are cleared so 32 and 64-bit counter implementations agree
+    fn keystream(&self, v: &[u8; 16]) -> Ctr<&C> {
+        let q = u128::from_be_bytes(*v) & !(1 << 63 | 1 << 31);
+        Ctr::new(&self.ctr, Split::Counter128, &q.to_be_bytes())
+    }
+}
+
+impl<C> KeySizeUser for Siv<C>
+where
+    C: KeySizeUser,
+    C::KeySize: Add,
+    Sum<C::KeySize, C::KeySize>: ArrayLength<u8>,
+{
+    type KeySize = Sum<C::KeySize, C::KeySize>;
+}
+
+/// The first half of the key is the S2V key, the second the CTR key.
+impl<C> KeyInit for Siv<C>
+where
+    C: BlockEncrypt<BlockSize = U16> + KeyInit + Clone,
+    C::KeySize: Add,
+    Sum<C::KeySize, C::KeySize>: ArrayLength<u8>,
+{
+    fn new(key: &Key<Self>) -> Self {
+        let (k1, k2) = key.split_at(C::key_size());
+        Siv::from_ciphers(C::new(GenericArray::from_slice(k1)), C::new(GenericArray::from_slice(k2)))
+    }
+}
+
+
+/// `Siv` behind the `aead` traits, with the associated data and a 16 byte
+/// nonce as the two headers. Sealed messages start with the tag.
+#[derive(Clone)]
+pub struct SivAead<C> {
+    siv: Siv<C>,
+}
+
+impl<C> From<Siv<C>> for SivAead<C> {
+    fn from(siv: Siv<C>) -> Self {
+        SivAead { siv }
+    }
+}
+
+impl<C> KeySizeUser for SivAead<C>
+where
+    Siv<C>: KeySizeUser,
+{
+    type KeySize = <Siv<C> as KeySizeUser>::KeySize;
+}
+
+impl<C> KeyInit for SivAead<C>
+where
+    Siv<C>: KeyInit,
+{
+    fn new(key: &Key<Self>) -> Self {
+        SivAead { siv: Siv::new(key) }
+    }
+}
+
+impl<C> AeadCore for SivAead<C> {
+    type NonceSize = U16;
+    type TagSize = U16;
+    type CiphertextOverhead = U0;
+}
+
+impl<C: BlockEncrypt<BlockSize = U16> + Clone> AeadInPlace for SivAead<C> {
+    fn encrypt_in_place(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut dyn Buffer,
+    ) -> aead::Result<()> {
+        // make room for the tag in front
+        let len = buffer.len();
+        buffer.extend_from_slice(&[0; BLOCK_SIZE])?;
+        buffer.as_mut().copy_within(..len, BLOCK_SIZE);
+        let tag = self.encrypt_in_place_detached(nonce, associated_data, &mut buffer.as_mut()[BLOCK_SIZE..])?;
+        buffer.as_mut()[..BLOCK_SIZE].copy_from_slice(&tag);
+        Ok(())
+    }
+
+    fn encrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+    ) -> aead::Result<Tag<Self>> {
+        let v = self.siv.encrypt_in_place_detached([associated_data, nonce.as_slice()], buffer)?;
+        Ok(v.into())
+    }
+
+    fn decrypt_in_place(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut dyn Buffer,
+    ) -> aead::Result<()> {
+        if buffer.len() < BLOCK_SIZE {
+            return Err(aead::Error);
+        }
+        let tag = Tag::<Self>::clone_from_slice(&buffer.as_ref()[..BLOCK_SIZE]);
+        self.decrypt_in_place_detached(nonce, associated_data, &mut buffer.as_mut()[BLOCK_SIZE..], &tag)?;
+        let len = buffer.len() - BLOCK_SIZE;
+        buffer.as_mut().copy_within(BLOCK_SIZE.., 0);
+        buffer.truncate(len);
+        Ok(())
+    }
+
+    fn decrypt_in_place_detached(
+        &self,
+        nonce: &Nonce<Self>,
+        associated_data: &[u8],
+        buffer: &mut [u8],
+        tag: &Tag<Self>,
+    ) -> aead::Result<()> {
+        self.siv.decrypt_in_place_detached([associated_data, nonce.as_slice()], buffer, tag.as_ref())
+    }
+}
+
+
+#[cfg(test)]
+use hex_literal::hex;
+
+// RFC 5297, A.1 and A.2
+#[test]
+fn test_aes_vectors(){
+    use aes::Aes128;
+
+    fn check(key: &[u8], headers: &[&[u8]], plain: &[u8], expected: &[u8]) {
+        let siv = Siv::<Aes128>::new_from_slice(key).unwrap();
+
+        let mut buffer = plain.to_vec();
+        let v = siv.encrypt_in_place_detached(headers, &mut buffer).unwrap();
+        assert_eq!(v, expected[..16]);
+        assert_eq!(buffer, expected[16..]);
+
+        siv.decrypt_in_place_detached(headers, &mut buffer, &v).unwrap();
+        assert_eq!(buffer, plain);
+
+        let mut bad = v;
+        bad[0] ^= 1;
+        let mut buffer = expected[16..].to_vec();
+        assert!(siv.decrypt_in_place_detached(headers, &mut buffer, &bad).is_err());
+        assert_eq!(buffer, expected[16..]);
+    }
+
+    check(
+        &hex!("fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0 f0f1f2f3f4f5f6f7f8f9fafbfcfdfeff"),
+        &[&hex!("101112131415161718191a1b1c1d1e1f2021222324252627")],
+        &hex!("112233445566778899aabbccddee"),
+        &hex!("85632d07c6e8f37f950acd320a2ecc93 40c02b9690c4dc04daef7f6afe5c"),
+    );
+
+    check(
+        &hex!("7f7e7d7c7b7a79787776757473727170 404142434445464748494a4b4c4d4e4f"),
+        &[
+            &hex!("00112233445566778899aabbccddeeffdeaddadadeaddadaffeeddccbbaa99887766554433221100"),
+            &hex!("102030405060708090a0"),
+            &hex!("09f911029d74e35bd84156c5635688c0"),
+        ],
+        &hex!("7468697320697320736f6d6520706c61696e7465787420746f20656e6372797074207573696e67205349562d414553"),
+        &hex!(
+            "7bdb6e3b432667eb06f4d14bff2fbd0f cb900f2fddbe404326601965c889bf17"
+            "dba77ceb094fa663b7a3f748ba8af829 ea64ad544a272e9c485b62a3fd5c0d"
+        ),
+    );
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_tekton(){
+    use crate::imp::{b128::Tekton128, b256::Tekton256};
+    use aead::{Aead, Payload};
+    use rand::Rng;
+
+    fn check<C: BlockEncrypt<BlockSize = U16> + Clone>(key: &[u8])
+    where
+        Siv<C>: KeyInit,
+    {
+        let siv = Siv::<C>::new_from_slice(key).unwrap();
+        let msg: Vec<u8> = (0..100).map(|_| rand::thread_rng().gen()).collect();
+
+        // deterministic, but every header counts and their boundaries too
+        let sealed = siv.encrypt([&b"table"[..], b"row 7"], &msg).unwrap();
+        assert_eq!(sealed, siv.encrypt([&b"table"[..], b"row 7"], &msg).unwrap());
+        assert_ne!(sealed, siv.encrypt([&b"table"[..], b"row 8"], &msg).unwrap());
+        assert_ne!(sealed, siv.encrypt([&b"tabler"[..], b"ow 7"], &msg).unwrap());
+        assert_ne!(sealed, siv.encrypt([&b"table"[..]], &msg).unwrap());
+
+        assert_eq!(siv.decrypt([&b"table"[..], b"row 7"], &sealed).unwrap(), msg);
+        assert!(siv.decrypt([&b"table"[..], b"row 8"], &sealed).is_err());
+        for i in 0..sealed.len() {
+            let mut bad = sealed.clone();
+            bad[i] ^= 0x80;
+            assert!(siv.decrypt([&b"table"[..], b"row 7"], &bad).is_err());
+        }
+
+        for len in [0, 1, 15, 16, 17, 32] {
+            let sealed = siv.encrypt([b"h"], &msg[..len]).unwrap();
+            assert_eq!(siv.decrypt([b"h"], &sealed).unwrap(), msg[..len]);
+        }
+
+        let headers = vec![&b""[..]; MAX_HEADERS + 1];
+        assert!(siv.encrypt(&headers, &msg).is_err());
+        assert!(siv.encrypt(&headers[1..], &msg).is_ok());
+
+        let aead = SivAead::from(siv.clone());
+        let nonce = Nonce::<SivAead<C>>::from(rand::thread_rng().gen::<[u8; 16]>());
+        let sealed = aead.encrypt(&nonce, Payload { msg: &msg, aad: b"ad" }).unwrap();
+        assert_eq!(sealed, siv.encrypt([&b"ad"[..], &nonce], &msg).unwrap());
+        assert_eq!(aead.decrypt(&nonce, Payload { msg: &sealed, aad: b"ad" }).unwrap(), msg);
+        assert!(aead.decrypt(&nonce, Payload { msg: &sealed, aad: b"da" }).is_err());
+        assert!(aead.decrypt(&nonce, Payload { msg: &sealed[..15], aad: b"ad" }).is_err());
+    }
+
+    let mut key = [0; 64];
+    rand::thread_rng().fill(&mut key);
+    check::<Tekton128>(&key[..32]);
+    check::<Tekton256>(&key);
+}
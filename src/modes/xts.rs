@@ -0,0 +1,286 @@
+use core::slice;
+
+use cipher::{consts::U16, BlockDecrypt, BlockEncrypt, KeyInit};
+
+use super::{blocks_mut, dbl, xor, Block, Error, BATCH, BLOCK_SIZE};
+
+
+/// XTS (IEEE 1619) over two instances of a 128-bit block cipher, one for the
+/// data and one for the tweak. Sectors are encrypted in place, their length is
+/// preserved and a partial last block is handled with ciphertext stealing.
+#[derive(Clone)]
+pub struct Xts<C> {
+    data: C,
+    tweak: C,
+}
+
+impl<C> Xts<C> {
+
+    /// Takes the two ciphers as they are. Unlike `new_from_slice` this does not
+    /// check that they are keyed differently, IEEE 1619 vector 1 uses one key
+    /// for both.
+    pub fn new(data_cipher: C, tweak_cipher: C) -> Self {
+        Xts { data: data_cipher, tweak: tweak_cipher }
+    }
+}
+
+impl<C: KeyInit> Xts<C> {
+
+    /// Splits `key` into the data key and the tweak key, so a Tekton128 pair
+    /// takes 32 bytes and Tekton256 64. Equal halves are rejected.
+    pub fn new_from_slice(key: &[u8]) -> Result<Self, Error> {
+        if key.len() != 2 * C::key_size() {
+            return Err(Error::InvalidLength);
+        }
+        let (k1, k2) = key.split_at(C::key_size());
+        if k1 == k2 {
+            return Err(Error::InvalidKey);
+        }
+        let data = C::new_from_slice(k1).map_err(|_| Error::InvalidLength)?;
+        let tweak = C::new_from_slice(k2).map_err(|_| Error::InvalidLength)?;
+        Ok(Xts::new(data, tweak))
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16>> Xts<C> {
+
+    /// Encrypts one sector in place, it must be at least one block long.
+    pub fn encrypt_sector(&self, sector: u128, data: &mut [u8]) -> Result<(), Error> {
+        if data.len() < BLOCK_SIZE {
+            return Err(Error::InvalidLength);
+        }
+        let mut t = self.initial_tweak(sector);
+        let r = data.len() % BLOCK_SIZE;
+        let (head, tail) = data.split_at_mut(data.len() - r);
+        let blocks = blocks_mut(head)?;
+
+        xex(&self.data, blocks, &mut t, |c, b| c.encrypt_blocks(b));
+
+        if r != 0 {
+            // the partial block takes the head of the last ciphertext block,
+            // which is replaced by the padded partial block encrypted with the next tweak
+            let last = blocks.last_mut().unwrap();
+            let mut stolen = *last;
+            stolen[..r].copy_from_slice(tail);
+            tail.copy_from_slice(&last[..r]);
+            xex(&self.data, slice::from_mut(&mut stolen), &mut t, |c, b| c.encrypt_blocks(b));
+            *last = stolen;
+        }
+        Ok(())
+    }
+
+    /// Encrypts consecutive sectors of `sector_size` bytes starting at `first_sector`,
+    /// the last one may be shorter but not below one block. `data` is left
+    /// untouched on error.
+    pub fn encrypt_area(&self, data: &mut [u8], sector_size: usize, first_sector: u128) -> Result<(), Error> {
+        check_area(data.len(), sector_size, first_sector)?;
+        for (i, sector) in data.chunks_mut(sector_size).enumerate() {
+            self.encrypt_sector(first_sector + i as u128, sector)?;
+        }
+        Ok(())
+    }
+
+    fn initial_tweak(&self, sector: u128) -> u128 {
+        let mut t = Block::from(sector.to_le_bytes());
+        self.tweak.encrypt_block(&mut t);
+        u128::from_le_bytes(t.into())
+    }
+}
+
+impl<C: BlockEncrypt<BlockSize = U16> + BlockDecrypt> Xts<C> {
+
+    /// Decrypts one sector in place, it must be at least one block long.
+    pub fn decrypt_sector(&self, sector: u128, data: &mut [u8]) -> Result<(), Error> {
+        if data.len() < BLOCK_SIZE {
+            return Err(Error::InvalidLength);
+        }
+        let mut t = self.initial_tweak(sector);
+        let r = data.len() % BLOCK_SIZE;
+        let (head, tail) = data.split_at_mut(data.len() - r);
+        let blocks = blocks_mut(head)?;
+
+        if r == 0 {
+            xex(&self.data, blocks, &mut t, |c, b| c.decrypt_blocks(b));
+            return Ok(());
+        }
+
+        let (full, last) = blocks.split_at_mut(blocks.len() - 1);
+        xex(&self.data, full, &mut t, |c, b| c.decrypt_blocks(b));
+
+        // the last whole block was encrypted with the tweak after its own
+        let mut next = dbl(t);
+        xex(&self.data, last, &mut next, |c, b| c.decrypt_blocks(b));
+        let last = &mut last[0];
+        let mut stolen = *last;
+        stolen[..r].copy_from_slice(tail);
+        tail.copy_from_slice(&last[..r]);
+        xex(&self.data, slice::from_mut(&mut stolen), &mut t, |c, b| c.decrypt_blocks(b));
+        *last = stolen;
+        Ok(())
+    }
+
+    /// Decrypts an area written by `encrypt_area` with the same `sector_size` and
+    /// `first_sector`. `data` is left untouched on error.
+    pub fn decrypt_area(&self, data: &mut [u8], sector_size: usize, first_sector: u128) -> Result<(), Error> {
+        check_area(data.len(), sector_size, first_sector)?;
+        for (i, sector) in data.chunks_mut(sector_size).enumerate() {
+            self.decrypt_sector(first_sector + i as u128, sector)?;
+        }
+        Ok(())
+    }
+}
+
+
+// Rejects an area before any sector is touched: sectors and the trailing one
+// must be at least a block long and the sector numbers must not overflow.
+fn check_area(len: usize, sector_size: usize, first_sector: u128) -> Result<(), Error> {
+    if sector_size < BLOCK_SIZE || (!len.is_multiple_of(sector_size) && len % sector_size < BLOCK_SIZE) {
+        return Err(Error::InvalidLength);
+    }
+    let sectors = len.div_ceil(sector_size) as u128;
+    if sectors > 0 && first_sector.checked_add(sectors - 1).is_none() {
+        return Err(Error::InvalidLength);
+    }
+    Ok(())
+}
+
+// XORs every block with its tweak around `f`, leaving `t` at the tweak of the next block.
+fn xex<C>(cipher: &C, blocks: &mut [Block], t: &mut u128, f: impl Fn(&C, &mut [Block])) {
+    for chunk in blocks.chunks_mut(BATCH) {
+        let mut tweaks = [[0; 16]; BATCH];
+        for (block, tweak) in chunk.iter_mut().zip(&mut tweaks) {
+            // tweaks are little-endian in IEEE 1619
+            *tweak = t.to_le_bytes();
+            xor(block, tweak);
+            *t = dbl(*t);
+        }
+        f(cipher, chunk);
+        for (block, tweak) in chunk.iter_mut().zip(&tweaks) {
+            xor(block, tweak);
+        }
+    }
+}
+
+
+#[cfg(test)]
+use hex_literal::hex;
+
+// IEEE 1619-2007 annex B, vectors 1 to 4 and 15 to 18
+#[test]
+fn test_aes_vectors(){
+    use aes::Aes128;
+
+    fn check(xts: &Xts<Aes128>, sector: u128, plain: &[u8], expected: &[u8]) {
+        let mut data = plain.to_vec();
+        xts.encrypt_sector(sector, &mut data).unwrap();
+        assert_eq!(data, expected);
+        xts.decrypt_sector(sector, &mut data).unwrap();
+        assert_eq!(data, plain);
+    }
+
+    // equal halves, only accepted through `new`
+    let zero = Aes128::new(&[0; 16].into());
+    check(&Xts::new(zero.clone(), zero), 0, &[0; 32],
+        &hex!("917cf69ebd68b2ec9b9fe9a3eadda692cd43d2f59598ed858c02c2652fbf922e"));
+
+    let xts = Xts::new_from_slice(&hex!("11111111111111111111111111111111 22222222222222222222222222222222")).unwrap();
+    check(&xts, 0x3333333333, &[0x44; 32],
+        &hex!("c454185e6a16936e39334038acef838bfb186fff7480adc4289382ecd6d394f0"));
+
+    let xts = Xts::new_from_slice(&hex!("fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0 22222222222222222222222222222222")).unwrap();
+    check(&xts, 0x3333333333, &[0x44; 32],
+        &hex!("af85336b597afc1a900b2eb21ec949d292df4c047e0b21532186a5971a227a89"));
+
+    let xts = Xts::new_from_slice(&hex!("27182818284590452353602874713526 31415926535897932384626433832795")).unwrap();
+    let plain: Vec<u8> = (0..512).map(|i| i as u8).collect();
+    check(&xts, 0, &plain, &hex!(
+        "27a7479befa1d476489f308cd4cfa6e2a96e4bbe3208ff25287dd3819616e89c"
+        "c78cf7f5e543445f8333d8fa7f56000005279fa5d8b5e4ad40e736ddb4d35412"
+        "328063fd2aab53e5ea1e0a9f332500a5df9487d07a5c92cc512c8866c7e860ce"
+        "93fdf166a24912b422976146ae20ce846bb7dc9ba94a767aaef20c0d61ad0265"
+        "5ea92dc4c4e41a8952c651d33174be51a10c421110e6d81588ede82103a252d8"
+        "a750e8768defffed9122810aaeb99f9172af82b604dc4b8e51bcb08235a6f434"
+        "1332e4ca60482a4ba1a03b3e65008fc5da76b70bf1690db4eae29c5f1badd03c"
+        "5ccf2a55d705ddcd86d449511ceb7ec30bf12b1fa35b913f9f747a8afd1b130e"
+        "94bff94effd01a91735ca1726acd0b197c4e5b03393697e126826fb6bbde8ecc"
+        "1e08298516e2c9ed03ff3c1b7860f6de76d4cecd94c8119855ef5297ca67e9f3"
+        "e7ff72b1e99785ca0a7e7720c5b36dc6d72cac9574c8cbbc2f801e23e56fd344"
+        "b07f22154beba0f08ce8891e643ed995c94d9a69c9f1b5f499027a78572aeebd"
+        "74d20cc39881c213ee770b1010e4bea718846977ae119f7a023ab58cca0ad752"
+        "afe656bb3c17256a9f6e9bf19fdd5a38fc82bbe872c5539edb609ef4f79c203e"
+        "bb140f2e583cb2ad15b4aa5b655016a8449277dbd477ef2c8d6c017db738b18d"
+        "eb4a427d1923ce3ff262735779a418f20a282df920147beabe421ee5319d0568"
+    ));
+
+    // partial last blocks, the data unit sequence number is 9a78563412 in little-endian
+    let xts = Xts::new_from_slice(&hex!("fffefdfcfbfaf9f8f7f6f5f4f3f2f1f0 bfbebdbcbbbab9b8b7b6b5b4b3b2b1b0")).unwrap();
+    let plain: Vec<u8> = (0..20).collect();
+    check(&xts, 0x123456789a, &plain[..17], &hex!("6c1625db4671522d3d7599601de7ca09ed"));
+    check(&xts, 0x123456789a, &plain[..18], &hex!("d069444b7a7e0cab09e24447d24deb1fedbf"));
+    check(&xts, 0x123456789a, &plain[..19], &hex!("e5df1351c0544ba1350b3363cd8ef4beedbf9d"));
+    check(&xts, 0x123456789a, &plain[..20], &hex!("9d84c813f719aa2c7be3f66171c7c5c2edbf9dac"));
+}
+
+#[test]
+fn test_tekton_sectors(){
+    use crate::imp::{b128::Tekton128, b256::Tekton256};
+    use rand::Rng;
+
+    let key: [u8; 32] = rand::thread_rng().gen();
+    let pair = Xts::<Tekton128>::new_from_slice(&key).unwrap();
+    let mut key = [0; 64];
+    rand::thread_rng().fill(&mut key);
+    let split = Xts::<Tekton256>::new_from_slice(&key).unwrap();
+
+    fn round_trip<C: BlockEncrypt<BlockSize = U16> + BlockDecrypt>(xts: &Xts<C>) {
+        for len in (16..100).chain([512, 4096, 4100]) {
+            let plain: Vec<u8> = (0..len).map(|_| rand::thread_rng().gen()).collect();
+
+            let mut enc = plain.clone();
+            xts.encrypt_sector(7, &mut enc).unwrap();
+            assert_ne!(enc, plain);
+
+            let mut other = plain.clone();
+            xts.encrypt_sector(8, &mut other).unwrap();
+            assert_ne!(enc, other);
+
+            xts.decrypt_sector(7, &mut enc).unwrap();
+            assert_eq!(enc, plain);
+        }
+
+        let mut area: Vec<u8> = (0..4 * 512 + 100).map(|_| rand::thread_rng().gen()).collect();
+        let plain = area.clone();
+        xts.encrypt_area(&mut area, 512, 40).unwrap();
+        for (i, sector) in plain.chunks(512).enumerate() {
+            let mut expected = sector.to_vec();
+            xts.encrypt_sector(40 + i as u128, &mut expected).unwrap();
+            assert_eq!(area[512 * i..][..sector.len()], expected);
+        }
+        xts.decrypt_area(&mut area, 512, 40).unwrap();
+        assert_eq!(area, plain);
+
+        assert_eq!(xts.encrypt_sector(0, &mut [0; 15]), Err(Error::InvalidLength));
+
+        // rejected areas are left as they were
+        let mut data = [7; 520];
+        assert_eq!(xts.encrypt_area(&mut data, 512, 0), Err(Error::InvalidLength));
+        assert_eq!(xts.decrypt_area(&mut data, 512, 0), Err(Error::InvalidLength));
+        assert_eq!(data, [7; 520]);
+        assert_eq!(xts.encrypt_area(&mut data, 0, 0), Err(Error::InvalidLength));
+        assert_eq!(xts.encrypt_area(&mut data, 15, 0), Err(Error::InvalidLength));
+        assert_eq!(xts.encrypt_area(&mut data[..512], 256, u128::MAX), Err(Error::InvalidLength));
+        assert_eq!(data, [7; 520]);
+
+        xts.encrypt_area(&mut data[..512], 256, u128::MAX - 1).unwrap();
+        xts.decrypt_area(&mut data[..512], 256, u128::MAX - 1).unwrap();
+        xts.encrypt_area(&mut data[..40], 20, 0).unwrap();
+        xts.encrypt_area(&mut [], 512, u128::MAX).unwrap();
+    }
+
+    round_trip(&pair);
+    round_trip(&split);
+
+    assert!(Xts::<Tekton128>::new_from_slice(&[1; 32]).is_err());
+    assert!(Xts::<Tekton128>::new_from_slice(&[1; 31]).is_err());
+    assert!(Xts::<Tekton256>::new_from_slice(&key[..32]).is_err());
+}
@@ -0,0 +1,167 @@
+use tekton::imp::b128::Tekton128;
+use tekton::imp::b256::Tekton256;
+
+use rand::{Rng};
+
+use aes::{Aes128, Aes256};
+use aes::cipher::{
+    BlockCipher, BlockEncrypt, BlockDecrypt, KeyInit, Key,
+    consts::U16, generic_array::GenericArray,
+};
+
+
+fn rand_key<C: KeyInit>() -> Key<C> {
+    let mut key = Key::<C>::default();
+    rand::thread_rng().fill(key.as_mut_slice());
+    key
+}
+
+
+fn conformance<C>()
+where
+    C: BlockCipher<BlockSize = U16> + BlockEncrypt + BlockDecrypt + KeyInit,
+{
+    let cipher = C::new(&rand_key::<C>());
+
+    for _ in 0..1000 {
+        let p: u128 = rand::thread_rng().gen();
+        let pb = GenericArray::from(p.to_be_bytes());
+
+        let mut enc = pb;
+        cipher.encrypt_block(&mut enc);
+        assert_ne!(enc, pb);
+
+        let mut dec = enc;
+        cipher.decrypt_block(&mut dec);
+        assert_eq!(dec, pb);
+
+        let mut enc_b2b = GenericArray::default();
+        cipher.encrypt_block_b2b(&pb, &mut enc_b2b);
+        assert_eq!(enc_b2b, enc);
+
+        let mut dec_b2b = GenericArray::default();
+        cipher.decrypt_block_b2b(&enc, &mut dec_b2b);
+        assert_eq!(dec_b2b, pb);
+    }
+
+    // lengths chosen to hit both the parallel chunks and the tail of every backend
+    for n in [0, 1, 3, 7, 8, 9, 16, 33, 100] {
+        let blocks: Vec<_> = (0..n)
+            .map(|_| GenericArray::from(rand::thread_rng().gen::<u128>().to_be_bytes()))
+            .collect();
+
+        let mut enc = blocks.clone();
+        cipher.encrypt_blocks(&mut enc);
+
+        for (p, c) in blocks.iter().zip(enc.iter()) {
+            let mut single = *p;
+            cipher.encrypt_block(&mut single);
+            assert_eq!(&single, c);
+        }
+
+        let mut enc_b2b = vec![GenericArray::default(); n];
+        cipher.encrypt_blocks_b2b(&blocks, &mut enc_b2b).unwrap();
+        assert_eq!(enc_b2b, enc);
+
+        let mut dec = enc.clone();
+        cipher.decrypt_blocks(&mut dec);
+        assert_eq!(dec, blocks);
+
+        let mut dec_b2b = vec![GenericArray::default(); n];
+        cipher.decrypt_blocks_b2b(&enc, &mut dec_b2b).unwrap();
+        assert_eq!(dec_b2b, blocks);
+    }
+
+    let key = rand_key::<C>();
+    let mut other_key = key.clone();
+    other_key[0] ^= 1;
+
+    let mut a = GenericArray::default();
+    let mut b = GenericArray::default();
+    C::new(&key).encrypt_block(&mut a);
+    C::new(&other_key).encrypt_block(&mut b);
+    assert_ne!(a, b);
+
+    let mut c = GenericArray::default();
+    C::new(&key).encrypt_block(&mut c);
+    assert_eq!(a, c);
+
+    assert!(C::new_from_slice(&[0; 7]).is_err());
+}
+
+
+#[test]
+fn test_conformance_aes(){
+    conformance::<Aes128>();
+    conformance::<Aes256>();
+}
+
+#[test]
+fn test_conformance_128(){
+    conformance::<Tekton128>();
+}
+
+#[test]
+fn test_conformance_256(){
+    conformance::<Tekton256>();
+}
+
+#[test]
+fn test_key_init_matches_inherent(){
+    use tekton::imp::Flags;
+
+    let key: u128 = rand::thread_rng().gen();
+    let p: u128 = rand::thread_rng().gen();
+
+    let mut block = GenericArray::from(p.to_be_bytes());
+    <Tekton128 as KeyInit>::new(&key.to_be_bytes().into()).encrypt_block(&mut block);
+
+    let mut payload = p.to_be_bytes();
+    Tekton128::new(key.to_be_bytes(), Flags::v2()).encrypt(&mut payload);
+
+    assert_eq!(block.as_slice(), &payload);
+
+
+    let mut key: [u8; 32] = [0; 32];
+    rand::thread_rng().fill(&mut key);
+
+    let mut block = GenericArray::from(p.to_be_bytes());
+    <Tekton256 as KeyInit>::new(&key.into()).encrypt_block(&mut block);
+
+    let mut payload = p.to_be_bytes();
+    Tekton256::new(key, Flags::v2()).encrypt(&mut payload);
+
+    assert_eq!(block.as_slice(), &payload);
+}
+
+fn shared_across_threads<C: tekton::imp::TektonCipher + 'static>(tekton: C){
+    use std::sync::Arc;
+    use std::thread;
+
+    let tekton = Arc::new(tekton);
+    let p: u128 = rand::thread_rng().gen();
+
+    let mut expected = p.to_be_bytes();
+    tekton.encrypt_block(&mut expected);
+
+    let workers: Vec<_> = (0..4).map(|_| {
+        let tekton = Arc::clone(&tekton);
+        thread::spawn(move || {
+            let mut enc = p.to_be_bytes();
+            tekton.encrypt_block(&mut enc);
+            enc
+        })
+    }).collect();
+
+    for worker in workers {
+        assert_eq!(worker.join().unwrap(), expected);
+    }
+}
+
+#[test]
+fn test_shared_across_threads(){
+    use tekton::imp::Flags;
+
+    shared_across_threads(Tekton128::new(rand::thread_rng().gen(), Flags::default()));
+    shared_across_threads(Tekton256::new(rand::thread_rng().gen(), Flags::default()));
+}
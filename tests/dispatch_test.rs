@@ -0,0 +1,57 @@
+use tekton::imp::b128::Tekton128;
+use tekton::imp::b256::Tekton256;
+use tekton::imp::dispatch::{self, Backend};
+use tekton::imp::{Error, Flags, KeySchedule, Rounds, TektonCipher};
+
+use rand::Rng;
+
+
+fn compare_backends<C: TektonCipher>(tekton: &C) {
+    // long enough for several pipeline passes plus every tail length
+    let blocks: Vec<[u8; 16]> = (0..77).map(|_| rand::thread_rng().gen()).collect();
+
+    let mut expected = blocks.clone();
+    for block in expected.iter_mut() {
+        tekton.encrypt_block(block);
+    }
+
+    for backend in Backend::ALL.into_iter().filter(|b| b.is_supported()) {
+        dispatch::force_backend(Some(backend)).unwrap();
+        assert_eq!(dispatch::backend(), backend);
+
+        for n in 0..blocks.len() {
+            let mut enc = blocks[..n].to_vec();
+            tekton.encrypt_blocks(&mut enc);
+            assert_eq!(enc, expected[..n], "{:?}, {} blocks", backend, n);
+
+            tekton.decrypt_blocks(&mut enc);
+            assert_eq!(enc, blocks[..n], "{:?}, {} blocks", backend, n);
+        }
+    }
+}
+
+
+// a single test, forcing the backend is process wide
+#[test]
+fn test_backends_agree(){
+    for rounds in [Rounds::FASTER, Rounds::SAFER, Rounds::Custom(1), Rounds::Custom(32)] {
+        for schedule in [KeySchedule::V1, KeySchedule::V2] {
+            let flags = || Flags { rounds, schedule };
+            compare_backends(&Tekton128::new(rand::thread_rng().gen(), flags()));
+            compare_backends(&Tekton256::new(rand::thread_rng().gen(), flags()));
+        }
+    }
+
+    for backend in Backend::ALL {
+        match dispatch::force_backend(Some(backend)) {
+            Ok(()) => assert!(backend.is_supported()),
+            Err(e) => assert_eq!(e, Error::UnsupportedBackend(backend)),
+        }
+    }
+
+    dispatch::force_backend(None).unwrap();
+    assert_eq!(dispatch::backend(), Backend::detect());
+    if cfg!(feature = "scalar") {
+        assert_eq!(Backend::detect(), Backend::Baseline);
+    }
+}
@@ -0,0 +1,117 @@
+use tekton::imp::b128::Tekton128;
+use tekton::imp::b256::Tekton256;
+use tekton::imp::{Flags, TektonCipher};
+use tekton::modes::{cfb::{Cfb128, Cfb8}, ctr::{Ctr, Split}, ecb, ofb::Ofb, BLOCK_SIZE};
+
+use cipher::{consts::U16, BlockEncrypt, StreamCipher};
+use rand::Rng;
+
+
+fn random_bytes(n: usize) -> Vec<u8> {
+    (0..n).map(|_| rand::thread_rng().gen()).collect()
+}
+
+// feeds `data` to `f` in random sized pieces
+fn in_pieces(data: &mut [u8], mut f: impl FnMut(&mut [u8])) {
+    let mut rest = data;
+    while !rest.is_empty() {
+        let n = rand::thread_rng().gen_range(0..=rest.len().min(70));
+        let (piece, tail) = rest.split_at_mut(n);
+        f(piece);
+        rest = tail;
+    }
+}
+
+
+fn round_trip<C: BlockEncrypt<BlockSize = U16> + Clone>(cipher: &C) {
+    let iv: [u8; 16] = rand::thread_rng().gen();
+
+    for len in [0, 1, 15, 16, 17, 100, 1000] {
+        let plain = random_bytes(len);
+
+        let mut data = plain.clone();
+        let mut cfb = Cfb8::new(cipher.clone(), &iv);
+        in_pieces(&mut data, |p| cfb.encrypt(p));
+        let mut cfb = Cfb8::new(cipher.clone(), &iv);
+        in_pieces(&mut data, |p| cfb.decrypt(p));
+        assert_eq!(data, plain);
+
+        let mut data = plain.clone();
+        let mut cfb = Cfb128::new(cipher.clone(), &iv);
+        in_pieces(&mut data, |p| cfb.encrypt(p));
+        let mut cfb = Cfb128::new(cipher.clone(), &iv);
+        in_pieces(&mut data, |p| cfb.decrypt(p));
+        assert_eq!(data, plain);
+
+        let mut data = plain.clone();
+        let mut ofb = Ofb::new(cipher.clone(), &iv);
+        in_pieces(&mut data, |p| ofb.apply_keystream(p));
+        if len > 0 {
+            assert_ne!(data, plain);
+        }
+        let mut ofb = Ofb::new(cipher.clone(), &iv);
+        in_pieces(&mut data, |p| ofb.apply_keystream(p));
+        assert_eq!(data, plain);
+    }
+}
+
+fn cross_mode<C: TektonCipher + BlockEncrypt<BlockSize = U16>>(cipher: &C) {
+    let iv: [u8; 16] = rand::thread_rng().gen();
+    let plain = random_bytes(16 * 40);
+
+    // the first block is E(IV) ^ P everywhere
+    let mut first = iv;
+    TektonCipher::encrypt_block(cipher, &mut first);
+    let first: Vec<u8> = first.iter().zip(&plain).map(|(k, p)| k ^ p).collect();
+
+    let mut cfb8 = plain.clone();
+    Cfb8::new(cipher.clone(), &iv).encrypt(&mut cfb8);
+    let mut cfb128 = plain.clone();
+    Cfb128::new(cipher.clone(), &iv).encrypt(&mut cfb128);
+    let mut ofb = plain.clone();
+    Ofb::new(cipher.clone(), &iv).apply_keystream(&mut ofb);
+    let mut ctr = plain.clone();
+    Ctr::new(cipher.clone(), Split::Counter128, &iv).apply_keystream(&mut ctr);
+
+    assert_eq!(cfb8[0], first[0]);
+    assert_eq!(cfb128[..BLOCK_SIZE], first);
+    assert_eq!(ofb[..BLOCK_SIZE], first);
+    assert_eq!(ctr[..BLOCK_SIZE], first);
+    assert_ne!(ofb[BLOCK_SIZE..], ctr[BLOCK_SIZE..]);
+
+    // OFB keystream is the IV encrypted over and over
+    let mut register = iv;
+    for (c, p) in ofb.chunks(BLOCK_SIZE).zip(plain.chunks(BLOCK_SIZE)) {
+        TektonCipher::encrypt_block(cipher, &mut register);
+        let block: Vec<u8> = register.iter().zip(p).map(|(k, p)| k ^ p).collect();
+        assert_eq!(c, block);
+    }
+
+    // CFB-128 keystream is the previous ciphertext block under ECB
+    let mut keystream = iv.to_vec();
+    keystream.extend_from_slice(&cfb128[..cfb128.len() - BLOCK_SIZE]);
+    ecb::encrypt(cipher, &mut keystream).unwrap();
+    let expected: Vec<u8> = keystream.iter().zip(&plain).map(|(k, p)| k ^ p).collect();
+    assert_eq!(cfb128, expected);
+}
+
+
+#[test]
+fn test_round_trip_128(){
+    round_trip(&Tekton128::new(rand::thread_rng().gen(), Flags::default()));
+}
+
+#[test]
+fn test_round_trip_256(){
+    round_trip(&Tekton256::new(rand::thread_rng().gen(), Flags::default()));
+}
+
+#[test]
+fn test_cross_mode_128(){
+    cross_mode(&Tekton128::new(rand::thread_rng().gen(), Flags::default()));
+}
+
+#[test]
+fn test_cross_mode_256(){
+    cross_mode(&Tekton256::new(rand::thread_rng().gen(), Flags::default()));
+}
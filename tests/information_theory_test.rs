@@ -1,4 +1,6 @@
 use tekton::imp::b128::Tekton128;
+use tekton::imp::b256::Tekton256;
+use tekton::imp::b128t::{Tekton128T, Tweak};
 use bitreader::BitReader;
 
 use rand::{Rng};
@@ -11,94 +13,136 @@ use aes::cipher::{
     generic_array::GenericArray,
 };
 
-use tekton::imp::{Flags, Rounds};
+use tekton::imp::{Flags, Rounds, TektonCipher};
 
 
 
 
 
-#[test]
-fn test_compare_statistics_128(){
-    let key: u128 = rand::thread_rng().gen();
+fn uniformness_t<C: TektonCipher>(tekton: &C, payload: &[[u8; 16]]) -> f64 {
+    let mut hist = Histogram::<1000>::new();
 
-    let mut rng = rand::thread_rng();
-    let normal = Normal::new(u128::MAX as f64, (u128::MAX as f64)/100 as f64).unwrap();
+    for p in payload {
+        let mut enc = *p;
+        tekton.encrypt_block(&mut enc);
+        hist.update(enc);
+    }
 
-    let mut payload: [[u8; 16]; 100_000] = [[0; 16]; 100_000];
+    hist.uniformness()
+}
 
-    for i in 0..100_000 {
-        let v: f64 = normal.sample(&mut rng);
-        payload[i] = (v as u128).to_le_bytes();
-    }
+fn diffusion_t<C: TektonCipher>(tekton: &C) -> f64 {
+    let mut c: [f64; 100] = [0.0; 100];
 
-    let mut enc: [[u8; 16]; 100_000] = [[0; 16]; 100_000];
+    for cj in c.iter_mut() {
+        let mut rng = rand::thread_rng();
+        let _p: u128 = rng.gen();
+        let p = _p.to_be_bytes();
 
+        let mut conf: [f64; 128] = [0.0; 128];
+        for (i, ci) in conf.iter_mut().enumerate() {
+            let mut p0 = p;
+            p0[i/8] &= !(1 << (i%8));
 
-    let kb = GenericArray::from(key.to_be_bytes());
+            let mut enc_p0 = p0;
+            tekton.encrypt_block(&mut enc_p0);
 
+            let mut p1 = p;
+            p1[i/8] |= 1 << (i%8);
 
-   
+            let mut enc_p1 = p1;
+            tekton.encrypt_block(&mut enc_p1);
 
-    let cipher = Aes128::new(&kb);
+            let mut rdr0 = BitReader::new(&enc_p0);
+            let mut rdr1 = BitReader::new(&enc_p1);
 
-    let mut uniformness_t = |tekton: Tekton128| {
-        let mut hist = Histogram::<1000>::new();
+            let mut different = 0;
+            for _ in 0..128 {
+                let b0 = rdr0.read_bool().unwrap();
+                let b1 = rdr1.read_bool().unwrap();
 
-        for i in 0..100_000 {
-            enc[i] = payload[i];
-            tekton.encrypt(&mut enc[i]);
-            hist.update(enc[i]);
+                if b0 != b1 {
+                    different += 1;
+                }
+            }
+            *ci = different as f64;
         }
 
-        hist.uniformness()
-    };
+        let conf: f64 = conf.into_iter().sum();
+        *cj = conf/128.0;
+    }
 
-    let diffusion_t = |tekton: Tekton128| {
-        
+    let avg: f64 = c.into_iter().sum();
+    avg/100.0
+}
 
-        let mut c: [f64; 100] = [0.0; 100];
+type Step = fn(u128) -> u128;
 
-        for j in 0..100 {
-            let mut rng = rand::thread_rng();
-            let _p: u128 = rng.gen();
-            let p = _p.to_be_bytes();
+/// Average Hamming distance and largest per-bit correlation between
+/// ciphertexts of the same plaintexts under tweaks `t` and `step(t)`.
+fn tweak_correlation_t(tekton: &Tekton128T, samples: usize, step: Step) -> (f64, f64) {
+    let mut rng = rand::thread_rng();
+    let mut distance = 0;
+    // per output bit: sum of c0, sum of c1, sum of c0 * c1
+    let mut sums = [[0u64; 3]; 128];
+
+    for _ in 0..samples {
+        let p: [u8; 16] = rng.gen();
+        let t: u128 = rng.gen();
+
+        let mut c0 = p;
+        tekton.encrypt_tweaked(&mut c0, t);
+        let mut c1 = p;
+        tekton.encrypt_tweaked(&mut c1, step(t));
+
+        let (c0, c1) = (u128::from_be_bytes(c0), u128::from_be_bytes(c1));
+        distance += (c0 ^ c1).count_ones() as u64;
+        for (i, s) in sums.iter_mut().enumerate() {
+            let (b0, b1) = ((c0 >> i) as u64 & 1, (c1 >> i) as u64 & 1);
+            s[0] += b0;
+            s[1] += b1;
+            s[2] += b0 & b1;
+        }
+    }
 
-            let mut conf: [f64; 128] = [0.0; 128];
-            for i in 0..128 {
-                let mut p0 = p.clone();
-                p0[i/8] = p0[i/8] & !(1 << (i%8));
+    let n = samples as f64;
+    let correlation = sums.iter().map(|&[x, y, xy]| {
+        let (mx, my) = (x as f64 / n, y as f64 / n);
+        let cov = xy as f64 / n - mx * my;
+        (cov / (mx * (1.0 - mx) * my * (1.0 - my)).sqrt()).abs()
+    }).fold(0.0, f64::max);
 
-                let mut enc_p0 = p0.clone();
-                tekton.encrypt(&mut enc_p0);
+    (distance as f64 / n, correlation)
+}
 
-                let mut p1 = p.clone();
-                p1[i/8] = p1[i/8] | (1 << (i%8));
+fn normal_payload(payload: &mut [[u8; 16]]) {
+    let mut rng = rand::thread_rng();
+    let normal = Normal::new(u128::MAX as f64, (u128::MAX as f64)/100_f64).unwrap();
 
-                let mut enc_p1 = p1.clone();
-                tekton.encrypt(&mut enc_p1);
+    for p in payload.iter_mut() {
+        let v: f64 = normal.sample(&mut rng);
+        *p = (v as u128).to_le_bytes();
+    }
+}
 
-                let mut rdr0 = BitReader::new(&enc_p0);
-                let mut rdr1 = BitReader::new(&enc_p1);
 
-                let mut different = 0;
-                for _ in 0..128 {
-                    let b0 = rdr0.read_bool().unwrap();
-                    let b1 = rdr1.read_bool().unwrap();
+#[test]
+fn test_compare_statistics_128(){
+    let key: u128 = rand::thread_rng().gen();
 
-                    if b0 != b1 {
-                        different += 1;
-                    }
-                }
-                conf[i] = different as f64;
-            }
+    let mut rng = rand::thread_rng();
+    let normal = Normal::new(u128::MAX as f64, (u128::MAX as f64)/100 as f64).unwrap();
 
-            let conf: f64 = conf.into_iter().sum();
-            c[j] = conf/128.0;
-        }
+    let mut payload: [[u8; 16]; 100_000] = [[0; 16]; 100_000];
+    normal_payload(&mut payload);
 
-        let avg: f64 = c.into_iter().sum();
-        return avg/100.0;
-    };
+
+    let kb = GenericArray::from(key.to_be_bytes());
+
+
+   
+
+    let cipher = Aes128::new(&kb);
 
     let mut uniformness_a = || {
         let mut hist = Histogram::<1000>::new();
@@ -155,16 +199,16 @@ fn test_compare_statistics_128(){
 
 
     let tekton_bp = Tekton128::new(key.to_be_bytes(),
-        Flags { rounds: Rounds::FASTER });
+        Flags { rounds: Rounds::FASTER, ..Default::default() });
 
-    let u = uniformness_t(tekton_bp);
+    let u = uniformness_t(&tekton_bp, &payload);
 
     println!("Tekton (128bit)(3x) uniformness: {0:?}", u);
 
     let tekton_br = Tekton128::new(key.to_be_bytes(),
-    Flags { rounds: Rounds::SAFER });
+    Flags { rounds: Rounds::SAFER, ..Default::default() });
 
-    let u = uniformness_t(tekton_br);
+    let u = uniformness_t(&tekton_br, &payload);
 
     println!("Tekton (128bit)(5x) uniformness: {0:?}", u);
 
@@ -176,16 +220,16 @@ fn test_compare_statistics_128(){
     println!("--------------");
 
     let tekton_bp = Tekton128::new(key.to_be_bytes(),
-    Flags { rounds: Rounds::FASTER });
+    Flags { rounds: Rounds::FASTER, ..Default::default() });
 
-    let u = diffusion_t(tekton_bp);
+    let u = diffusion_t(&tekton_bp);
 
     println!("Tekton (128bit)(3x) confusion: {0:?}", u);
 
     let tekton_br = Tekton128::new(key.to_be_bytes(),
-    Flags { rounds: Rounds::SAFER});
+    Flags { rounds: Rounds::SAFER, ..Default::default() });
 
-    let u = diffusion_t(tekton_br);
+    let u = diffusion_t(&tekton_br);
 
     println!("Tekton (128bit)(5x) confusion: {0:?}", u);
 
@@ -193,4 +237,57 @@ fn test_compare_statistics_128(){
 
     println!("AES (128bit) confusion: {0:?}", u);
     
-}
\ No newline at end of file
+}
+
+
+#[test]
+fn test_compare_statistics_256(){
+    let mut key: [u8; 32] = [0; 32];
+    rand::thread_rng().fill(&mut key);
+
+    let mut payload: [[u8; 16]; 100_000] = [[0; 16]; 100_000];
+    normal_payload(&mut payload);
+
+    let tekton_bp = Tekton256::new(key, Flags { rounds: Rounds::FASTER, ..Default::default() });
+    let tekton_br = Tekton256::new(key, Flags { rounds: Rounds::SAFER, ..Default::default() });
+
+    println!("Tekton (256bit)(6x) uniformness: {0:?}", uniformness_t(&tekton_bp, &payload));
+    println!("Tekton (256bit)(8x) uniformness: {0:?}", uniformness_t(&tekton_br, &payload));
+
+    println!("--------------");
+
+    println!("Tekton (256bit)(6x) confusion: {0:?}", diffusion_t(&tekton_bp));
+    println!("Tekton (256bit)(8x) confusion: {0:?}", diffusion_t(&tekton_br));
+}
+
+
+#[test]
+fn test_adjacent_tweaks(){
+    let samples = 100_000;
+    // |r| of uncorrelated bits stays within a few 1/sqrt(n), for the largest of 128 too
+    let bound = 5.0 / (samples as f64).sqrt();
+
+    for rounds in [Rounds::FASTER, Rounds::SAFER] {
+        let tekton = Tekton128T::new(rand::thread_rng().gen(), Flags { rounds, ..Default::default() });
+
+        let steps: [(&str, Step); 4] = [
+            ("t + 1", |t| t.wrapping_add(1)),
+            ("t ^ 1", |t| t ^ 1),
+            ("t ^ 1 << 63", |t| t ^ 1 << 63),
+            ("t ^ 1 << 127", |t| t ^ 1 << 127),
+        ];
+        for (name, step) in steps {
+            let (distance, correlation) = tweak_correlation_t(&tekton, samples, step);
+            println!("Tekton128T ({0:?}) {name}: distance {distance:.3}, max |r| {correlation:.5}", rounds);
+            assert!((distance - 64.0).abs() < 0.2, "{name}: {distance}");
+            assert!(correlation < bound, "{name}: {correlation}");
+        }
+    }
+
+    // 64-bit tweaks are the low half of the 128-bit ones
+    let tekton = Tekton128T::new(rand::thread_rng().gen(), Flags::default());
+    let (mut a, mut b) = ([7; 16], [7; 16]);
+    tekton.encrypt_tweaked(&mut a, 42u64);
+    tekton.encrypt_tweaked(&mut b, Tweak::from(42u128));
+    assert_eq!(a, b);
+}
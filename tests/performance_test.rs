@@ -1,5 +1,3 @@
-#![feature(portable_simd)]
-
 use tekton::imp::b128::Tekton128;
 use tekton::imp::b256::Tekton256;
 
@@ -62,8 +60,21 @@ fn test_compare_perfomances_128(){
         }
     };
 
+    let mut nonces: Vec<[u8; 16]> = (0..1_000_000_u128).map(|i| i.to_be_bytes()).collect();
+
+    let mut work_i = |tekton: &Tekton128| {
+        tekton.encrypt_blocks(std::hint::black_box(&mut nonces));
+        tekton.decrypt_blocks(std::hint::black_box(&mut nonces));
+    };
+
     let tekton_bp = Tekton128::new(key.to_be_bytes(),
-        Flags { rounds: Rounds::FASTER });
+        Flags { rounds: Rounds::FASTER, ..Default::default() });
+
+    let start = Instant::now();
+    work_i(&tekton_bp);
+    let duration = start.elapsed();
+
+    println!("Tekton (128bit)(3x) interleaved: 1M nonces: {0:?}", duration);
 
     let start = Instant::now();
     work_t(tekton_bp);
@@ -72,7 +83,13 @@ fn test_compare_perfomances_128(){
     println!("Tekton (128bit)(3x): 1M nonces: {0:?}", duration);
 
     let tekton_br = Tekton128::new(key.to_be_bytes(),
-    Flags { rounds: Rounds::SAFER });
+    Flags { rounds: Rounds::SAFER, ..Default::default() });
+
+    let start = Instant::now();
+    work_i(&tekton_br);
+    let duration = start.elapsed();
+
+    println!("Tekton (128bit)(5x) interleaved: 1M nonces: {0:?}", duration);
 
     let start = Instant::now();
     work_t(tekton_br);
@@ -105,21 +122,104 @@ fn test_compare_perfomances_128(){
         }
     });
 
+    let mut nonces: Vec<[u8; 16]> = (0..1_000_000_u128).map(|i| i.to_be_bytes()).collect();
+
+    let mut work_i = |tekton: &Tekton256| {
+        tekton.encrypt_blocks(std::hint::black_box(&mut nonces));
+        tekton.decrypt_blocks(std::hint::black_box(&mut nonces));
+    };
+
     let tekton_bp = Tekton256::new(key,
-        Flags { rounds: Rounds::FASTER });
+        Flags { rounds: Rounds::FASTER, ..Default::default() });
+
+    let start = Instant::now();
+    work_i(&tekton_bp);
+    let duration = start.elapsed();
+
+    println!("Tekton (256bit)(6x) interleaved: 1M nonces: {0:?}", duration);
 
     let start = Instant::now();
     work_t(tekton_bp);
     let duration = start.elapsed();
 
-    println!("Tekton (256bit)(5x): 1M nonces: {0:?}", duration);
+    println!("Tekton (256bit)(6x): 1M nonces: {0:?}", duration);
 
     let tekton_br = Tekton256::new(key,
-    Flags { rounds: Rounds::SAFER});
+    Flags { rounds: Rounds::SAFER, ..Default::default() });
+
+    let start = Instant::now();
+    work_i(&tekton_br);
+    let duration = start.elapsed();
+
+    println!("Tekton (256bit)(8x) interleaved: 1M nonces: {0:?}", duration);
 
     let start = Instant::now();
     work_t(tekton_br);
     let duration = start.elapsed();
 
     println!("Tekton (256bit)(8x): 1M nonces: {0:?}", duration);
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_batch_throughput(){
+    use std::hint::black_box;
+    use tekton::imp::TektonCipher;
+
+    fn per_block<C: TektonCipher>(name: &str, tekton: C) {
+        for batch in [1, 2, 4, 8, 16, 64] {
+            let mut blocks: Vec<[u8; 16]> = (0..batch)
+                .map(|_| rand::thread_rng().gen::<u128>().to_be_bytes())
+                .collect();
+
+            let iterations = 1_000_000 / batch;
+
+            let start = Instant::now();
+            for _ in 0..iterations {
+                tekton.encrypt_blocks(black_box(&mut blocks));
+            }
+            let duration = start.elapsed();
+
+            println!("{0} batch {1:>2}: {2:?}/block", name, batch, duration / (iterations * batch) as u32);
+        }
+    }
+
+    let key: u128 = rand::thread_rng().gen();
+    per_block("Tekton (128bit)(5x)", Tekton128::new(key.to_be_bytes(),
+        Flags { rounds: Rounds::SAFER, ..Default::default() }));
+
+    per_block("Tekton (256bit)(8x)", Tekton256::new(rand_u256(),
+        Flags { rounds: Rounds::SAFER, ..Default::default() }));
+}
+
+#[test]
+fn test_mac_throughput(){
+    use std::hint::black_box;
+    use tekton::modes::{cmac::Cmac, pmac::Pmac};
+    use aes::cipher::KeySizeUser;
+    use digest::Mac;
+
+    fn mac<M: Mac + KeySizeUser + Clone>(name: &str, mac: M) {
+        for kib in [1, 16, 64, 256, 1024] {
+            let msg: Vec<u8> = (0..kib * 1024).map(|_| rand::thread_rng().gen()).collect();
+            let iterations = 16 * 1024 / kib;
+
+            let start = Instant::now();
+            for _ in 0..iterations {
+                black_box(mac.clone().chain_update(black_box(&msg)).finalize());
+            }
+            let duration = start.elapsed();
+
+            println!("{0} {1:>4} KiB: {2:?}/message, {3:.0} MiB/s", name, kib, duration / iterations as u32,
+                (iterations * kib) as f64 / 1024.0 / duration.as_secs_f64());
+        }
+    }
+
+    let key: [u8; 16] = rand::thread_rng().gen();
+    let tekton = Tekton128::new(key, Flags { rounds: Rounds::SAFER, ..Default::default() });
+    mac("CMAC Tekton (128bit)(5x)", Cmac::from(tekton.clone()));
+    mac("PMAC Tekton (128bit)(5x)", Pmac::from(tekton));
+
+    let aes = Aes128::new(&key.into());
+    mac("CMAC AES (128bit)", Cmac::from(aes.clone()));
+    mac("PMAC AES (128bit)", Pmac::from(aes));
+}